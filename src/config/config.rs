@@ -1,5 +1,10 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 
+use crate::runner::ErrorType;
+use super::{HttpConfig, Threshold, AbortCondition};
+
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum Body {
     Json(String),
@@ -20,26 +25,140 @@ pub struct Url {
     pub args: Vec<String>,
 }
 
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // total number of attempts, including the first one
+    pub max_attempts: usize,
+    // milliseconds before the first retry, doubled after every attempt up to max_backoff
+    pub backoff: usize,
+    pub max_backoff: usize,
+    // waits a random time between 0 and the computed backoff
+    pub jitter: bool,
+    // failed attempts are retried when they match any of the error types or status codes
+    pub on_errors: Vec<ErrorType>,
+    pub on_status: Vec<u16>,
+}
+
 #[derive(Clone, Debug)]
 pub struct RequestDetails {
+    // reported id of the request, defaults to the method and the url template, e.g. "GET https://sosnowski.dev/post/{slug}"
+    pub name: Option<String>,
     pub url: Url,
     pub method: HttpMethod,
     pub data: Option<Vec<RequestData>>,
     pub repeat: Option<usize>,
+    pub retry: Option<RetryPolicy>,
+}
+
+// milliseconds
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum ThinkTime {
+    Constant(usize),
+    Uniform {
+        min: usize,
+        max: usize,
+    },
+    Normal {
+        mean: usize,
+        std_dev: usize,
+    },
+    Exponential {
+        mean: usize,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Condition {
+    // status code of the most recent response
+    LastStatus(u16),
+    LastRequestFailed,
+    // variable set earlier with Task::Set
+    Variable {
+        name: String,
+        value: String,
+    },
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
 
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum LoopCondition {
+    Count(usize),
+    // checked before every iteration, stops after max_iterations when set
+    While {
+        condition: Condition,
+        max_iterations: Option<usize>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Branch {
+    pub weight: usize,
+    pub tasks: Vec<Task>,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum Task {
     Request(RequestDetails),
-    Wait(usize)
+    // seconds
+    Wait(usize),
+    Think(ThinkTime),
+    Loop {
+        condition: LoopCondition,
+        tasks: Vec<Task>,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Task>,
+        otherwise: Vec<Task>,
+    },
+    // runs one of the branches, picked at random according to the weights
+    RandomChoice(Vec<Branch>),
+    // runs at most once per user, even when nested in a loop
+    Once {
+        name: String,
+        tasks: Vec<Task>,
+    },
+    Set {
+        name: String,
+        value: String,
+    },
+    // reported as a whole (end-to-end duration, failed when any request inside failed)
+    // in addition to the requests it contains
+    Group {
+        name: String,
+        tasks: Vec<Task>,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct Schedule {
     pub tasks: Vec<Task>,
+    // default pause before every request except the first one
+    pub think_time: Option<ThinkTime>,
+    // seconds, a user runs the schedule (one iteration) for at least that long,
+    // so new iterations start at most every `pacing` seconds per user
+    pub pacing: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    // relative share of users running this scenario, ignored when it has its own workload
+    pub weight: usize,
+    pub schedule: Schedule,
+    // drives a separate pool of users running only this scenario, in parallel with the main workload
+    pub workload: Option<Workload>,
+    // seconds after the test start when the scenario's own workload begins
+    pub start_offset: usize,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum HttpMethod {
     GET,
@@ -49,6 +168,7 @@ pub enum HttpMethod {
 }
 
 // https://stackoverflow.com/questions/8316882/what-is-an-easing-function
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum Workload {
     Constant {
@@ -76,10 +196,188 @@ pub enum Workload {
 
 
 
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    pub requests_per_second: usize,
+    // requests allowed at once after an idle period
+    pub burst: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    // shared by all users of the run
+    pub global: Option<RateLimit>,
+    // separate limit for every user
+    pub per_user: Option<RateLimit>,
+    // keyed by request name, shared by all users
+    pub per_request: HashMap<String, RateLimit>,
+}
+
+impl RateLimitConfig {
+    pub fn new() -> RateLimitConfig {
+        return RateLimitConfig {
+            global: None,
+            per_user: None,
+            per_request: HashMap::new(),
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ResultsFormat {
+    Ndjson,
+    Csv,
+}
+
+#[derive(Clone, Debug)]
+pub enum PushTarget {
+    Http(String),
+    Udp(SocketAddr),
+}
+
+impl PushTarget {
+    // http(s)://host/path or udp://host:port
+    pub fn parse(value: &str) -> Result<PushTarget, String> {
+        if let Some(address) = value.strip_prefix("udp://") {
+            return address.parse()
+                .map(PushTarget::Udp)
+                .map_err(|err| format!("Invalid UDP address {}: {}", address, err));
+        }
+        if value.starts_with("http://") || value.starts_with("https://") {
+            return Ok(PushTarget::Http(value.to_string()));
+        }
+        return Err(format!("Unsupported push target {}, expected http(s):// or udp://", value));
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PushConfig {
+    // seconds between pushes, metrics are buffered in between
+    pub flush_interval: usize,
+    // lines per payload, larger batches are split into several payloads
+    pub max_batch_size: usize,
+}
+
+impl PushConfig {
+    pub fn new() -> PushConfig {
+        return PushConfig {
+            flush_interval: 5,
+            max_batch_size: 5000,
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ReporterConfig {
+    // live view redrawn in the terminal
+    Terminal,
+    // summary written at the end of the run, see docs/json_report.md
+    Json {
+        path: String,
+    },
+    // static page with charts over time and per-request tables
+    Html {
+        path: String,
+    },
+    // thresholds as test cases, for CI test result views
+    Junit {
+        path: String,
+    },
+    // metrics endpoint scraped while the test runs, http://<address>/metrics
+    Prometheus {
+        address: SocketAddr,
+    },
+    // InfluxDB line protocol, one point per request
+    Influx {
+        target: PushTarget,
+        // sent as "Authorization: Token <token>", required by InfluxDB 2
+        token: Option<String>,
+        push: PushConfig,
+    },
+    // StatsD over UDP, with DogStatsD tags instead of labels encoded in the metric names
+    Statsd {
+        address: SocketAddr,
+        prefix: String,
+        dogstatsd: bool,
+        push: PushConfig,
+    },
+    // OpenTelemetry metrics, OTLP over HTTP with JSON encoding
+    Otlp {
+        url: String,
+        push: PushConfig,
+    },
+    // every request as it finishes, for offline analysis
+    Results {
+        path: String,
+        format: ResultsFormat,
+    },
+}
+
+impl ReporterConfig {
+    // name used on the command line with an optional output path, e.g. --reporter json:report.json
+    pub fn parse(value: &str) -> Result<ReporterConfig, String> {
+        let (name, path) = match value.split_once(':') {
+            Some((name, path)) => (name, Some(path.to_string())),
+            None => (value, None),
+        };
+
+        return match name {
+            "terminal" => Ok(ReporterConfig::Terminal),
+            "json" => Ok(ReporterConfig::Json {
+                path: path.unwrap_or_else(|| "report.json".to_string()),
+            }),
+            "html" => Ok(ReporterConfig::Html {
+                path: path.unwrap_or_else(|| "report.html".to_string()),
+            }),
+            "junit" => Ok(ReporterConfig::Junit {
+                path: path.unwrap_or_else(|| "junit.xml".to_string()),
+            }),
+            "prometheus" => Ok(ReporterConfig::Prometheus {
+                address: path.unwrap_or_else(|| "127.0.0.1:9464".to_string()).parse()
+                    .map_err(|err| format!("Invalid Prometheus address: {}", err))?,
+            }),
+            "influx" => Ok(ReporterConfig::Influx {
+                target: PushTarget::parse(&path.unwrap_or_else(|| "http://127.0.0.1:8086/write?db=swarm".to_string()))?,
+                token: None,
+                push: PushConfig::new(),
+            }),
+            "statsd" | "dogstatsd" => Ok(ReporterConfig::Statsd {
+                address: path.unwrap_or_else(|| "127.0.0.1:8125".to_string()).parse()
+                    .map_err(|err| format!("Invalid StatsD address: {}", err))?,
+                prefix: "swarm".to_string(),
+                dogstatsd: name == "dogstatsd",
+                push: PushConfig::new(),
+            }),
+            "otlp" => Ok(ReporterConfig::Otlp {
+                url: path.unwrap_or_else(|| "http://127.0.0.1:4318/v1/metrics".to_string()),
+                push: PushConfig::new(),
+            }),
+            "ndjson" => Ok(ReporterConfig::Results {
+                path: path.unwrap_or_else(|| "results.ndjson".to_string()),
+                format: ResultsFormat::Ndjson,
+            }),
+            "csv" => Ok(ReporterConfig::Results {
+                path: path.unwrap_or_else(|| "results.csv".to_string()),
+                format: ResultsFormat::Csv,
+            }),
+            _ => Err(format!("Unknown reporter {}", name)),
+        };
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub workload: Workload,
-    pub schedule: Schedule,
+    pub scenarios: Vec<Scenario>,
+    pub http: HttpConfig,
+    pub rate_limit: RateLimitConfig,
+    pub reporters: Vec<ReporterConfig>,
+    // pass/fail criteria of the run, any failed threshold makes the process exit with 1
+    pub thresholds: Vec<Threshold>,
+    // checked every second, the first one crossed stops the run gracefully
+    pub abort_conditions: Vec<AbortCondition>,
+    // seconds covered by one time window of the results, the "current" stats and the exported time series
+    pub window_size: usize,
 }
 
 impl Config {
@@ -90,109 +388,161 @@ impl Config {
                 max_users: 30,
                 ramp_up_time: 20,
             },
-            schedule: Schedule {
-                tasks: vec![
-                    Task::Request(RequestDetails {
-                        method: HttpMethod::GET,
-                        url: Url {
-                            url: "https://sosnowski.dev".to_string(),
-                            args: vec![],
-                        },
-                        data: None,
-                        repeat: Some(10),
-                    }),
-                    Task::Request(RequestDetails {
-                        method: HttpMethod::GET,
-                        url: Url {
-                            url: "https://sosnowski.dev/post/monetizing-your-blog-with-cryptocurrencies".to_string(),
-                            args: vec![],
-                        },
-                        data: None,
-                        repeat: Some(10),
-                    }),
-                    Task::Request(RequestDetails {
-                        method: HttpMethod::GET,
-                        url: Url {
-                            url: "https://sosnowski.dev/post/static-serverless-site-with-nextjs".to_string(),
-                            args: vec![],
-                        },
-                        data: None,
-                        repeat: Some(10),
-                    }),
-                    Task::Request(RequestDetails {
-                        method: HttpMethod::GET,
-                        url: Url {
-                            url: "https://sosnowski.dev/post/anatomy-of-aws-lambda".to_string(),
-                            args: vec![],
-                        },
-                        data: None,
-                        repeat: None,
-                    }),
-                    // Task::Request(RequestDetails {
-                    //     method: HttpMethod::GET,
-                    //     url: Url {
-                    //         url: "http://localhost:3000/test_get/{param1}/{param2}".to_string(),
-                    //         args: vec![
-                    //             "param1".to_string(),
-                    //             "param2".to_string()
-                    //         ],
-                    //     },
-                    //     data: vec![
-                    //         RequestData {
-                    //             params: Some(HashMap::from([
-                    //                 ("param1".to_string(), "aaaaaaaaaaaaaa11111".to_string()),
-                    //                 ("param2".to_string(), "bbbbbbbbbbbbbb11111".to_string()),
-                    //             ])),
-                    //             headers: Some(HashMap::from([
-                    //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
-                    //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
-                    //             ])),
-                    //             query: Some(HashMap::from([
-                    //                 ("key1".to_string(), "value1".to_string()),
-                    //                 ("key2".to_string(), "value2".to_string()),
-                    //             ])),
-                    //             body: None,
-                    //         },
-                    //         RequestData {
-                    //             params: Some(HashMap::from([
-                    //                 ("param1".to_string(), "aaaaaaaaaaaaaa222222".to_string()),
-                    //                 ("param2".to_string(), "bbbbbbbbbbbbbb222222".to_string()),
-                    //             ])),
-                    //             headers: Some(HashMap::from([
-                    //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
-                    //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
-                    //             ])),
-                    //             query: Some(HashMap::from([
-                    //                 ("key1".to_string(), "value1".to_string()),
-                    //                 ("key2".to_string(), "value2".to_string()),
-                    //             ])),
-                    //             body: None,
-                    //         },
-                    //     ]
-                    // }),
-                    // Task::Request(RequestDetails {
-                    //     method: HttpMethod::POST,
-                    //     url: Url {
-                    //         url: "http://localhost:3000/test_post".to_string(),
-                    //         args: vec![],
-                    //     },
-                    //     data: vec![
-                    //         RequestData {
-                    //             params: None,
-                    //             headers: Some(HashMap::from([
-                    //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
-                    //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
-                    //             ])),
-                    //             query: Some(HashMap::from([
-                    //                 ("key1".to_string(), "value1".to_string()),
-                    //                 ("key2".to_string(), "value2".to_string()),
-                    //             ])),
-                    //             body: Some(Body::Json(r#"{"body-key": "body-value"}"#.to_string())),
-                    //         }
-                    //     ]
-                    // })
-                ]
-            }
+            scenarios: vec![
+                Scenario {
+                    name: "default".to_string(),
+                    weight: 1,
+                    workload: None,
+                    start_offset: 0,
+                    schedule: Schedule {
+                        tasks: vec![
+                            Task::Request(RequestDetails {
+                                name: None,
+                                method: HttpMethod::GET,
+                                url: Url {
+                                    url: "https://sosnowski.dev".to_string(),
+                                    args: vec![],
+                                },
+                                data: None,
+                                repeat: Some(10),
+                                retry: None,
+                            }),
+                            Task::Request(RequestDetails {
+                                name: None,
+                                method: HttpMethod::GET,
+                                url: Url {
+                                    url: "https://sosnowski.dev/post/monetizing-your-blog-with-cryptocurrencies".to_string(),
+                                    args: vec![],
+                                },
+                                data: None,
+                                repeat: Some(10),
+                                retry: None,
+                            }),
+                            Task::Request(RequestDetails {
+                                name: None,
+                                method: HttpMethod::GET,
+                                url: Url {
+                                    url: "https://sosnowski.dev/post/static-serverless-site-with-nextjs".to_string(),
+                                    args: vec![],
+                                },
+                                data: None,
+                                repeat: Some(10),
+                                retry: None,
+                            }),
+                            Task::Request(RequestDetails {
+                                name: None,
+                                method: HttpMethod::GET,
+                                url: Url {
+                                    url: "https://sosnowski.dev/post/anatomy-of-aws-lambda".to_string(),
+                                    args: vec![],
+                                },
+                                data: None,
+                                repeat: None,
+                                retry: None,
+                            }),
+                            // Task::Request(RequestDetails {
+                            //     method: HttpMethod::GET,
+                            //     url: Url {
+                            //         url: "http://localhost:3000/test_get/{param1}/{param2}".to_string(),
+                            //         args: vec![
+                            //             "param1".to_string(),
+                            //             "param2".to_string()
+                            //         ],
+                            //     },
+                            //     data: vec![
+                            //         RequestData {
+                            //             params: Some(HashMap::from([
+                            //                 ("param1".to_string(), "aaaaaaaaaaaaaa11111".to_string()),
+                            //                 ("param2".to_string(), "bbbbbbbbbbbbbb11111".to_string()),
+                            //             ])),
+                            //             headers: Some(HashMap::from([
+                            //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
+                            //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
+                            //             ])),
+                            //             query: Some(HashMap::from([
+                            //                 ("key1".to_string(), "value1".to_string()),
+                            //                 ("key2".to_string(), "value2".to_string()),
+                            //             ])),
+                            //             body: None,
+                            //         },
+                            //         RequestData {
+                            //             params: Some(HashMap::from([
+                            //                 ("param1".to_string(), "aaaaaaaaaaaaaa222222".to_string()),
+                            //                 ("param2".to_string(), "bbbbbbbbbbbbbb222222".to_string()),
+                            //             ])),
+                            //             headers: Some(HashMap::from([
+                            //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
+                            //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
+                            //             ])),
+                            //             query: Some(HashMap::from([
+                            //                 ("key1".to_string(), "value1".to_string()),
+                            //                 ("key2".to_string(), "value2".to_string()),
+                            //             ])),
+                            //             body: None,
+                            //         },
+                            //     ]
+                            // }),
+                            // Task::Request(RequestDetails {
+                            //     method: HttpMethod::POST,
+                            //     url: Url {
+                            //         url: "http://localhost:3000/test_post".to_string(),
+                            //         args: vec![],
+                            //     },
+                            //     data: vec![
+                            //         RequestData {
+                            //             params: None,
+                            //             headers: Some(HashMap::from([
+                            //                 ("user-agent".to_string(), "awesome rust swarm".to_string()),
+                            //                 ("x-custom-header".to_string(), "custom-header-value".to_string())
+                            //             ])),
+                            //             query: Some(HashMap::from([
+                            //                 ("key1".to_string(), "value1".to_string()),
+                            //                 ("key2".to_string(), "value2".to_string()),
+                            //             ])),
+                            //             body: Some(Body::Json(r#"{"body-key": "body-value"}"#.to_string())),
+                            //         }
+                            //     ]
+                            // })
+                        ],
+                        think_time: None,
+                        pacing: None,
+                    },
+                },
+            ],
+            http: HttpConfig::new(),
+            rate_limit: RateLimitConfig::new(),
+            reporters: vec![ReporterConfig::Terminal],
+            thresholds: vec![],
+            abort_conditions: vec![],
+            window_size: 1,
         };
     }
+
+    // command line overrides, every --reporter <name> replaces the configured reporters
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let mut reporters = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--reporter" => {
+                    let name = args.next().ok_or("Missing reporter name after --reporter")?;
+                    reporters.push(ReporterConfig::parse(name)?);
+                },
+                "--window" => {
+                    let size = args.next().ok_or("Missing number of seconds after --window")?;
+                    self.window_size = size.parse()
+                        .map_err(|_| format!("Invalid window size {}", size))?;
+                    if self.window_size == 0 {
+                        return Err("Window size must be at least 1 second".to_string());
+                    }
+                },
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        if !reporters.is_empty() {
+            self.reporters = reporters;
+        }
+        return Ok(());
+    }
 }
\ No newline at end of file
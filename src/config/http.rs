@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum ClientIdentity {
+    // PEM encoded certificate chain and PKCS #8 private key
+    Pem {
+        certificate: String,
+        key: String,
+    },
+    Pkcs12 {
+        path: String,
+        password: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    // paths to PEM encoded CA certificates trusted on top of the system roots
+    pub root_certificates: Vec<String>,
+    pub identity: Option<ClientIdentity>,
+    // accept invalid certificates and hostnames, only for self-signed test servers
+    pub insecure_skip_verify: bool,
+    pub min_version: Option<TlsVersion>,
+    // server name sent in the handshake (and verified) instead of the url host
+    pub sni: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn new() -> TlsConfig {
+        return TlsConfig {
+            root_certificates: vec![],
+            identity: None,
+            insecure_skip_verify: false,
+            min_version: None,
+            sni: None,
+        };
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum HttpVersion {
+    // HTTP/1.1 only
+    Http1,
+    // HTTP/2 negotiated with ALPN over TLS, falls back to HTTP/1.1 (plain http stays on HTTP/1.1)
+    Http2,
+    // HTTP/2 without negotiation, also used for h2c over plain http
+    Http2PriorKnowledge,
+}
+
+#[derive(Clone, Debug)]
+pub struct Http2Config {
+    // number of users multiplexing their requests as streams over one connection, with any pool but PerRequest,
+    // None puts all users on a single connection per host with a shared pool and gives every user its own otherwise
+    pub streams_per_connection: Option<usize>,
+    pub adaptive_window: bool,
+    pub initial_stream_window_size: Option<u32>,
+    pub initial_connection_window_size: Option<u32>,
+}
+
+impl Http2Config {
+    pub fn new() -> Http2Config {
+        return Http2Config {
+            streams_per_connection: None,
+            adaptive_window: false,
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionPool {
+    // every user keeps its own connections, like a browser
+    PerUser,
+    // all users share connections, like a gateway
+    Shared,
+    // no keep-alive, every request opens a new connection
+    PerRequest,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConnectionConfig {
+    pub pool: ConnectionPool,
+    // idle connections kept per host
+    pub max_idle: Option<usize>,
+    // seconds
+    pub idle_timeout: Option<usize>,
+}
+
+impl ConnectionConfig {
+    pub fn new() -> ConnectionConfig {
+        return ConnectionConfig {
+            pool: ConnectionPool::PerUser,
+            max_idle: None,
+            idle_timeout: None,
+        };
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum ProxyKind {
+    // forwards plain http requests in absolute-form and tunnels https with HTTP CONNECT,
+    // hosts overrides don't apply to forwarded requests, the proxy resolves their host
+    Http,
+    Socks5,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    // host:port
+    pub address: String,
+    pub auth: Option<ProxyAuth>,
+    // hosts connected to directly, ".example.com" also matches subdomains, "*" matches everything
+    pub no_proxy: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DnsConfig {
+    // addresses used instead of resolving the host name, like /etc/hosts
+    pub hosts: HashMap<String, Vec<IpAddr>>,
+    // rotate through all resolved addresses instead of always trying the first one
+    pub round_robin: bool,
+}
+
+impl DnsConfig {
+    pub fn new() -> DnsConfig {
+        return DnsConfig {
+            hosts: HashMap::new(),
+            round_robin: false,
+        };
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompressionConfig {
+    // encodings advertised with accept-encoding, nothing is advertised when empty
+    pub accept: Vec<Encoding>,
+    // compresses Body::Json request bodies
+    pub request_body: Option<Encoding>,
+}
+
+impl CompressionConfig {
+    pub fn new() -> CompressionConfig {
+        return CompressionConfig {
+            accept: vec![],
+            request_body: None,
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    pub tls: TlsConfig,
+    pub http_version: HttpVersion,
+    pub http2: Http2Config,
+    pub connection: ConnectionConfig,
+    pub proxy: Option<ProxyConfig>,
+    pub dns: DnsConfig,
+    // source addresses for outgoing connections, users are spread across them round-robin
+    pub local_addresses: Vec<IpAddr>,
+    pub compression: CompressionConfig,
+}
+
+impl HttpConfig {
+    pub fn new() -> HttpConfig {
+        return HttpConfig {
+            tls: TlsConfig::new(),
+            http_version: HttpVersion::Http1,
+            http2: Http2Config::new(),
+            connection: ConnectionConfig::new(),
+            proxy: None,
+            dns: DnsConfig::new(),
+            local_addresses: vec![],
+            compression: CompressionConfig::new(),
+        };
+    }
+}
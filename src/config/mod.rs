@@ -1,3 +1,7 @@
 mod config;
+mod http;
+mod thresholds;
 
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use http::*;
+pub use thresholds::*;
\ No newline at end of file
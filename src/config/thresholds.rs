@@ -0,0 +1,87 @@
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Metric {
+    // percentage of failed requests
+    ErrorRate,
+    // durations of successful requests in milliseconds
+    MeanDuration,
+    P50,
+    P90,
+    P95,
+    P99,
+    MaxDuration,
+    // requests per second over the whole run
+    Rps,
+    Requests,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum ThresholdScope {
+    // all requests of the run
+    Global,
+    // requests with the given name, across all scenarios
+    Request(String),
+    Scenario(String),
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Limit {
+    Max(f64),
+    Min(f64),
+}
+
+// requests needed before a metric is judged during the run, ratios and latencies of a handful say nothing yet
+pub const DEFAULT_MIN_REQUESTS: usize = 20;
+
+#[derive(Clone, Debug)]
+pub struct Threshold {
+    pub metric: Metric,
+    pub scope: ThresholdScope,
+    pub limit: Limit,
+    // also checked every second during the run, failing once fails the threshold,
+    // a minimum of requests on continuous thresholds is rejected as the count only grows
+    pub continuous: bool,
+    // continuous checks report the threshold as pending until the scope has that many requests,
+    // ignored for the requests metric and at the end of the run, DEFAULT_MIN_REQUESTS by default
+    pub min_requests: usize,
+}
+
+#[allow(dead_code)]
+impl Threshold {
+    pub fn new(metric: Metric, scope: ThresholdScope, limit: Limit, continuous: bool) -> Threshold {
+        return Threshold {
+            metric,
+            scope,
+            limit,
+            continuous,
+            min_requests: DEFAULT_MIN_REQUESTS,
+        };
+    }
+}
+
+// stops the run when the metric over the last `window` seconds crosses the limit
+#[derive(Clone, Debug)]
+pub struct AbortCondition {
+    pub metric: Metric,
+    pub scope: ThresholdScope,
+    pub limit: Limit,
+    pub window: usize,
+    // windows with fewer requests are not judged, except for the rps and requests metrics,
+    // so a collapsed throughput still aborts the run, DEFAULT_MIN_REQUESTS by default
+    pub min_requests: usize,
+}
+
+#[allow(dead_code)]
+impl AbortCondition {
+    pub fn new(metric: Metric, scope: ThresholdScope, limit: Limit, window: usize) -> AbortCondition {
+        return AbortCondition {
+            metric,
+            scope,
+            limit,
+            window,
+            min_requests: DEFAULT_MIN_REQUESTS,
+        };
+    }
+}
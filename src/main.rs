@@ -3,16 +3,57 @@ mod runner;
 mod reporter;
 mod user;
 
+use config::ReporterConfig;
+
+// invalid arguments or configuration, a failed threshold exits with 1
+fn exit_with_error(error: String) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(2);
+}
+
 #[tokio::main]
 async fn main() {
     println!("Start");
-    let config = config::Config::new();
+    let mut config = config::Config::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    config.apply_args(&args).unwrap_or_else(|err| exit_with_error(err));
 
-    let mut runner = runner::Runner::start(config.clone());
+    let mut runner = runner::Runner::start(config.clone()).unwrap_or_else(|err| exit_with_error(err));
 
-    let mut reporter = reporter::CmdReporter::start(config.clone(), runner.take_receiver().unwrap());
+    let mut reporter = reporter::ReporterHub::start(config.clone(), runner.take_receiver().unwrap(), runner.stopper())
+        .unwrap_or_else(|err| exit_with_error(err));
 
     runner.wait_until_finished().await;
-    reporter.wait_until_finished().await;
+    let results = reporter.wait_until_finished().await;
     println!("End");
+
+    if results.num_of_reporter_errors > 0 {
+        println!("Reporter errors: {}", results.num_of_reporter_errors);
+        for error in results.reporter_errors.iter() {
+            println!("\t {}", error);
+        }
+    }
+
+    // the terminal report already ends with the abort reason and the thresholds
+    let terminal = config.reporters.iter().any(|reporter| matches!(reporter, ReporterConfig::Terminal));
+    let thresholds = &results.thresholds;
+    let failed = thresholds.iter().filter(|threshold| !threshold.passed).count();
+    if !terminal {
+        if let Some(reason) = &results.abort_reason {
+            println!("Aborted: {}", reason);
+        }
+        if !thresholds.is_empty() {
+            println!("Thresholds: {} passed, {} failed", thresholds.len() - failed, failed);
+            for threshold in thresholds.iter() {
+                match &threshold.failure {
+                    Some(failure) => println!("\t FAILED {}", failure),
+                    None => println!("\t passed {}", threshold.name),
+                }
+            }
+        }
+    }
+
+    if results.abort_reason.is_some() || failed > 0 {
+        std::process::exit(1);
+    }
 }
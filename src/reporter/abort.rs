@@ -0,0 +1,139 @@
+use crate::config::{AbortCondition, Metric};
+use crate::runner::RunnerStopper;
+use super::{AggregatedResults, check_limit};
+
+// Watches the abort conditions over the trailing time windows of the results and stops the runner
+// when one of them is crossed.
+pub struct AbortMonitor {
+    conditions: Vec<AbortCondition>,
+    stopper: RunnerStopper,
+    reason: Option<String>,
+}
+
+impl AbortMonitor {
+    pub fn new(conditions: &[AbortCondition], stopper: RunnerStopper) -> AbortMonitor {
+        return AbortMonitor {
+            conditions: conditions.to_vec(),
+            stopper,
+            reason: None,
+        };
+    }
+
+    // called every second, returns the abort reason once a condition was crossed
+    pub fn tick(&mut self, results: &AggregatedResults) -> Option<String> {
+        if self.reason.is_some() {
+            return self.reason.clone();
+        }
+
+        for condition in self.conditions.iter() {
+            // judged only over a full window, so a few early requests can't abort the run
+            let (sample, seconds) = match results.trailing_sample(&condition.scope, condition.window) {
+                Some(trailing) => trailing,
+                None => continue,
+            };
+            let enough_requests = match condition.metric {
+                Metric::Rps | Metric::Requests => true,
+                _ => sample.requests >= condition.min_requests.max(1),
+            };
+            if !enough_requests {
+                continue;
+            }
+
+            let result = check_limit(&condition.metric, &condition.scope, &condition.limit, &sample, seconds);
+            if let Some(failure) = result.failure {
+                let reason = format!("{} over the last {}s", failure, seconds);
+                self.stopper.stop(&reason);
+                self.reason = Some(reason);
+                break;
+            }
+        }
+        return self.reason.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::config::{Limit, ThresholdScope};
+    use crate::runner::{ErrorType, ReportMessage, TaskResult};
+
+    // a run whose first `seconds` windows are closed
+    fn run(seconds: u64) -> AggregatedResults {
+        let mut results = AggregatedResults::new(1);
+        results.started_at = SystemTime::now() - Duration::from_millis(seconds * 1000 + 900);
+        return results;
+    }
+
+    fn add_requests(results: &mut AggregatedResults, second: u64, requests: Vec<TaskResult>) {
+        let started_at = results.started_at + Duration::from_secs(second);
+        let requests = requests.into_iter()
+            .map(|mut request| {
+                request.started_at = started_at;
+                return request;
+            })
+            .collect();
+        results.add(ReportMessage { current_users: 1, results: vec![Ok(requests)], duration: second as usize });
+    }
+
+    fn slow_requests(count: usize) -> Vec<TaskResult> {
+        return (0..count).map(|_| TaskResult::request("main", "GET /users", 500)).collect();
+    }
+
+    #[test]
+    fn latencies_need_the_minimum_of_requests() {
+        let (stopper, stopped) = RunnerStopper::new();
+        let condition = AbortCondition::new(Metric::P95, ThresholdScope::Global, Limit::Max(100.0), 2);
+        let mut monitor = AbortMonitor::new(&[condition], stopper);
+        let mut results = run(2);
+
+        add_requests(&mut results, 0, slow_requests(5));
+        add_requests(&mut results, 1, slow_requests(5));
+        assert_eq!(monitor.tick(&results), None);
+        assert_eq!(*stopped.borrow(), None);
+
+        add_requests(&mut results, 1, slow_requests(15));
+        let reason = Some("p95 of all requests = 500ms > 100ms over the last 2s".to_string());
+        assert_eq!(monitor.tick(&results), reason);
+        assert_eq!(*stopped.borrow(), reason);
+    }
+
+    #[test]
+    fn the_minimum_of_requests_is_configurable() {
+        let (stopper, _stopped) = RunnerStopper::new();
+        let mut condition = AbortCondition::new(Metric::ErrorRate, ThresholdScope::Global, Limit::Max(10.0), 1);
+        condition.min_requests = 1;
+        let mut monitor = AbortMonitor::new(&[condition], stopper);
+        let mut results = run(1);
+
+        let failed = TaskResult::request("main", "GET /users", 0).failed(Some(500), ErrorType::Request5xx);
+        add_requests(&mut results, 0, vec![failed]);
+        assert_eq!(monitor.tick(&results), Some("error rate of all requests = 100% > 10% over the last 1s".to_string()));
+    }
+
+    #[test]
+    fn collapsed_throughput_aborts_the_run() {
+        let (stopper, stopped) = RunnerStopper::new();
+        let condition = AbortCondition::new(Metric::Rps, ThresholdScope::Global, Limit::Min(10.0), 2);
+        let mut monitor = AbortMonitor::new(&[condition], stopper);
+        let mut results = run(1);
+
+        add_requests(&mut results, 0, slow_requests(50));
+        add_requests(&mut results, 1, slow_requests(50));
+        // judged only once the whole window is over
+        assert_eq!(monitor.tick(&results), None);
+        results.started_at -= Duration::from_secs(1);
+        assert_eq!(monitor.tick(&results), None);
+        results.started_at -= Duration::from_secs(1);
+        assert_eq!(monitor.tick(&results), None);
+
+        results.started_at -= Duration::from_secs(1);
+        let reason = Some("rps of all requests = 0/s < 10/s over the last 2s".to_string());
+        assert_eq!(monitor.tick(&results), reason);
+        assert_eq!(*stopped.borrow(), reason);
+        // the first reason is kept
+        add_requests(&mut results, 3, slow_requests(50));
+        assert_eq!(monitor.tick(&results), reason);
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+use crate::config::ThresholdScope;
+use crate::runner::{ReportMessage, ErrorType, TaskResult, TaskKind};
+use super::{ThresholdResult, matches_scope};
+
+#[derive(Clone, Debug)]
+pub struct UrlResults {
+    pub num_of_requests: usize,
+    pub durations: HashMap<usize, usize>,
+    pub num_of_errors: usize,
+    // failed attempts followed by a retry, the rest of the errors are final failures
+    pub num_of_retried: usize,
+    pub error_types: HashMap<ErrorType, usize>,
+    pub http_versions: HashMap<String, usize>,
+    pub new_connections: usize,
+    pub reused_connections: usize,
+    pub bytes_received: usize,
+    pub bytes_decoded: usize,
+    // requests delayed by the rate limiters and the total time they waited (ms)
+    pub num_of_throttled: usize,
+    pub throttled_time: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceResults {
+    pub num_of_requests: usize,
+    pub num_of_errors: usize,
+    pub error_types: HashMap<ErrorType, usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupResults {
+    pub num_of_runs: usize,
+    pub num_of_errors: usize,
+    // end-to-end durations of successful runs
+    pub durations: HashMap<usize, usize>,
+    pub error_types: HashMap<ErrorType, usize>,
+    // group this one is nested in
+    pub parent: Option<String>,
+    // ids of the requests sent directly in the group
+    pub requests: HashSet<String>,
+}
+
+impl GroupResults {
+    fn new() -> GroupResults {
+        return GroupResults {
+            num_of_runs: 0,
+            num_of_errors: 0,
+            durations: HashMap::new(),
+            error_types: HashMap::new(),
+            parent: None,
+            requests: HashSet::new(),
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScenarioResults {
+    pub num_of_requests: usize,
+    pub num_of_errors: usize,
+    pub url_results: HashMap<String, UrlResults>,
+    pub group_results: HashMap<String, GroupResults>,
+}
+
+impl ScenarioResults {
+    fn new() -> ScenarioResults {
+        return ScenarioResults {
+            num_of_requests: 0,
+            num_of_errors: 0,
+            url_results: HashMap::new(),
+            group_results: HashMap::new(),
+        };
+    }
+}
+
+// requests of a time window or a threshold scope, merged across requests
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub requests: usize,
+    pub errors: usize,
+    // successful requests only
+    pub durations: HashMap<usize, usize>,
+}
+
+impl Sample {
+    pub fn new() -> Sample {
+        return Sample {
+            requests: 0,
+            errors: 0,
+            durations: HashMap::new(),
+        };
+    }
+
+    pub fn add(&mut self, result: &TaskResult) {
+        self.requests += 1;
+        if result.error {
+            self.errors += 1;
+        } else {
+            *self.durations.entry(result.duration).or_insert(0) += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Sample) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        for (duration, counter) in other.durations.iter() {
+            *self.durations.entry(*duration).or_insert(0) += counter;
+        }
+    }
+}
+
+// results are sent by the runner every 200ms, as soon as they finish
+const DELIVERY_DELAY: Duration = Duration::from_millis(500);
+
+// Requests that finished within one time window of the run.
+#[derive(Clone, Debug)]
+pub struct TimeWindow {
+    // seconds since the start of the run
+    pub start: usize,
+    pub size: usize,
+    // the most users running at once
+    pub users: usize,
+    pub total: Sample,
+    // keyed by scenario and request id, for scoped abort conditions
+    pub requests: HashMap<(String, String), Sample>,
+}
+
+impl TimeWindow {
+    fn new(start: usize, size: usize) -> TimeWindow {
+        return TimeWindow {
+            start,
+            size,
+            users: 0,
+            total: Sample::new(),
+            requests: HashMap::new(),
+        };
+    }
+
+    fn add(&mut self, result: &TaskResult) {
+        self.total.add(result);
+        self.requests.entry((result.scenario.clone(), result.id.clone()))
+            .or_insert_with(Sample::new)
+            .add(result);
+    }
+
+    pub fn rps(&self) -> f64 {
+        return self.total.requests as f64 / self.size as f64;
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        return error_rate(self.total.errors, self.total.requests);
+    }
+
+    pub fn sample(&self, scope: &ThresholdScope) -> Sample {
+        if let ThresholdScope::Global = scope {
+            return self.total.clone();
+        }
+        let mut sample = Sample::new();
+        for ((scenario, id), request_sample) in self.requests.iter() {
+            if matches_scope(scope, scenario, id) {
+                sample.merge(request_sample);
+            }
+        }
+        return sample;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AggregatedResults {
+    pub num_of_failed_users: usize,
+    pub current_users: usize,
+    pub duration: usize,
+
+    // keyed by scenario name
+    pub scenario_results: HashMap<String, ScenarioResults>,
+    // keyed by local source address
+    pub source_results: HashMap<String, SourceResults>,
+    // continuous thresholds during the run, all of them in the final results
+    pub thresholds: Vec<ThresholdResult>,
+    // set when an abort condition stopped the run early
+    pub abort_reason: Option<String>,
+    pub started_at: SystemTime,
+    // requests by the time they finished, one window every window_size seconds since the start
+    pub window_size: usize,
+    pub windows: Vec<TimeWindow>,
+    // errors returned by the reporters, only the most recent ones are kept
+    pub num_of_reporter_errors: usize,
+    pub reporter_errors: Vec<String>,
+}
+
+const MAX_REPORTER_ERRORS: usize = 5;
+
+impl AggregatedResults {
+    pub fn new(window_size: usize) -> AggregatedResults {
+        return AggregatedResults {
+            num_of_failed_users: 0,
+            current_users: 0,
+            duration: 0,
+            scenario_results: HashMap::new(),
+            source_results: HashMap::new(),
+            thresholds: vec![],
+            abort_reason: None,
+            started_at: SystemTime::now(),
+            window_size: window_size.max(1),
+            windows: vec![],
+            num_of_reporter_errors: 0,
+            reporter_errors: vec![],
+        };
+    }
+
+    pub fn add_reporter_error(&mut self, error: String) {
+        self.num_of_reporter_errors += 1;
+        self.reporter_errors.push(error);
+        if self.reporter_errors.len() > MAX_REPORTER_ERRORS {
+            self.reporter_errors.remove(0);
+        }
+    }
+
+    fn window_mut(&mut self, second: usize) -> &mut TimeWindow {
+        let index = second / self.window_size;
+        while self.windows.len() <= index {
+            let start = self.windows.len() * self.window_size;
+            self.windows.push(TimeWindow::new(start, self.window_size));
+        }
+        return &mut self.windows[index];
+    }
+
+    // Number of windows that are over, the current one is still being filled.
+    // A window is over once the results that finished in it had the time to reach the hub.
+    pub fn closed_windows(&self) -> usize {
+        let elapsed = self.started_at.elapsed().unwrap_or(Duration::ZERO).saturating_sub(DELIVERY_DELAY);
+        return elapsed.as_secs() as usize / self.window_size;
+    }
+
+    pub fn last_closed_window(&self) -> Option<&TimeWindow> {
+        return self.closed_windows().checked_sub(1).and_then(|index| self.windows.get(index));
+    }
+
+    // Requests of the scope over the closed windows covering the last `seconds`, rounded up to whole windows,
+    // with the number of seconds they cover. None until that many windows are over.
+    pub fn trailing_sample(&self, scope: &ThresholdScope, seconds: usize) -> Option<(Sample, usize)> {
+        let count = seconds.max(1).div_ceil(self.window_size);
+        let closed = self.closed_windows();
+        if closed < count {
+            return None;
+        }
+
+        let mut sample = Sample::new();
+        for window in self.windows.iter().take(closed).skip(closed - count) {
+            sample.merge(&window.sample(scope));
+        }
+        return Some((sample, count * self.window_size));
+    }
+
+    fn aggregate_windows(&mut self, results: &[TaskResult]) {
+        for result in results.iter().filter(|result| result.kind == TaskKind::Request) {
+            let finished_at = result.started_at + Duration::from_millis(result.duration as u64);
+            let second = finished_at.duration_since(self.started_at).map(|elapsed| elapsed.as_secs() as usize).unwrap_or(0);
+            self.window_mut(second).add(result);
+        }
+    }
+
+    pub fn add(&mut self, message: ReportMessage) {
+        self.current_users = message.current_users;
+        self.duration = message.duration;
+
+        let window = self.window_mut(message.duration);
+        window.users = window.users.max(message.current_users);
+
+        for user_result in message.results.into_iter() {
+            match user_result {
+                Ok(results) => {
+                    self.aggregate_windows(&results);
+                    aggregate_source_results(&mut self.source_results, &results);
+                    aggregate_results(&mut self.scenario_results, results);
+                },
+                Err(_) => {
+                    self.num_of_failed_users += 1;
+                }
+            }
+        }
+    }
+}
+
+// percentage of failed requests
+pub fn error_rate(errors: usize, requests: usize) -> f64 {
+    if requests == 0 {
+        return 0.0;
+    }
+    return errors as f64 / requests as f64 * 100.0;
+}
+
+// escapes text for html and xml reports, in content and in quoted attributes
+pub fn escape_xml(value: &str) -> String {
+    return value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+}
+
+// mean of a durations frequency table
+pub fn mean(durations: &HashMap<usize, usize>) -> usize {
+    let count: usize = durations.values().sum();
+    let sum: usize = durations.iter().map(|(duration, counter)| duration * counter).sum();
+    return sum.checked_div(count).unwrap_or(0);
+}
+
+// nearest-rank percentile of a durations frequency table, p between 0 and 100
+pub fn percentile(durations: &HashMap<usize, usize>, p: f64) -> usize {
+    let total: usize = durations.values().sum();
+    if total == 0 {
+        return 0;
+    }
+
+    let rank = ((p / 100.0) * total as f64).ceil().max(1.0) as usize;
+    let mut keys: Vec<&usize> = durations.keys().collect();
+    keys.sort();
+
+    let mut seen = 0;
+    for duration in keys {
+        seen += durations[duration];
+        if seen >= rank {
+            return *duration;
+        }
+    }
+    return 0;
+}
+
+fn aggregate_source_results(source_results: &mut HashMap<String, SourceResults>, results: &[TaskResult]) {
+    for result in results.iter() {
+        if let Some(local_addr) = &result.local_addr {
+            let entry = source_results.entry(local_addr.clone()).or_insert(SourceResults {
+                num_of_requests: 0,
+                num_of_errors: 0,
+                error_types: HashMap::new(),
+            });
+
+            entry.num_of_requests += 1;
+            if result.error {
+                entry.num_of_errors += 1;
+                *entry.error_types.entry(result.error_type.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+fn aggregate_results(scenario_results: &mut HashMap<String, ScenarioResults>, results: Vec<TaskResult>) {
+    
+    for result in results.into_iter() {
+        let scenario = scenario_results.entry(result.scenario.clone()).or_insert_with(ScenarioResults::new);
+
+        if result.kind == TaskKind::Group {
+            let entry = scenario.group_results.entry(result.id).or_insert_with(GroupResults::new);
+            entry.num_of_runs += 1;
+            entry.parent = result.group;
+            if result.error {
+                entry.num_of_errors += 1;
+                *entry.error_types.entry(result.error_type).or_insert(0) += 1;
+            } else {
+                *entry.durations.entry(result.duration).or_insert(0) += 1;
+            }
+            continue;
+        }
+
+        if let Some(group) = &result.group {
+            scenario.group_results.entry(group.clone()).or_insert_with(GroupResults::new)
+                .requests.insert(result.id.clone());
+        }
+
+        scenario.num_of_requests += 1;
+        if result.error {
+            scenario.num_of_errors += 1;
+        }
+
+        let entry = scenario.url_results.entry(result.id).or_insert(UrlResults {
+            num_of_requests: 0,
+            num_of_errors: 0,
+            num_of_retried: 0,
+            durations: HashMap::new(),
+            error_types: HashMap::new(),
+            http_versions: HashMap::new(),
+            new_connections: 0,
+            reused_connections: 0,
+            bytes_received: 0,
+            bytes_decoded: 0,
+            num_of_throttled: 0,
+            throttled_time: 0,
+        });
+
+        entry.num_of_requests += 1;
+        if result.retried {
+            entry.num_of_retried += 1;
+        }
+        entry.bytes_received += result.bytes_received;
+        entry.bytes_decoded += result.bytes_decoded;
+        if result.throttled > 0 {
+            entry.num_of_throttled += 1;
+            entry.throttled_time += result.throttled;
+        }
+        if let Some(http_version) = result.http_version {
+            *entry.http_versions.entry(http_version).or_insert(0) += 1;
+        }
+        match result.reused_connection {
+            Some(true) => entry.reused_connections += 1,
+            Some(false) => entry.new_connections += 1,
+            None => {},
+        }
+        if result.error {
+            entry.num_of_errors += 1;
+            let error_type_counter = entry.error_types.entry(result.error_type).or_insert(0);
+            *error_type_counter += 1;
+        } else {
+            // increment duration index in durations frequency table
+            let duration = result.duration;
+
+            if let Some(counter) = entry.durations.get_mut(&result.duration) {
+                *counter += 1;
+            } else {
+                entry.durations.insert(duration, 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn durations(pairs: &[(usize, usize)]) -> HashMap<usize, usize> {
+        return pairs.iter().copied().collect();
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let durations = durations(&[(10, 1), (20, 1), (30, 1), (40, 1)]);
+        assert_eq!(percentile(&durations, 0.0), 10);
+        assert_eq!(percentile(&durations, 25.0), 10);
+        assert_eq!(percentile(&durations, 50.0), 20);
+        assert_eq!(percentile(&durations, 51.0), 30);
+        assert_eq!(percentile(&durations, 95.0), 40);
+        assert_eq!(percentile(&durations, 100.0), 40);
+    }
+
+    #[test]
+    fn percentile_counts_repeated_durations() {
+        let durations = durations(&[(5, 98), (100, 2)]);
+        assert_eq!(percentile(&durations, 98.0), 5);
+        assert_eq!(percentile(&durations, 99.0), 100);
+    }
+
+    #[test]
+    fn percentile_of_nothing_is_zero() {
+        assert_eq!(percentile(&HashMap::new(), 95.0), 0);
+    }
+
+    fn results_with_windows(elapsed: u64, requests: &[usize]) -> AggregatedResults {
+        let mut results = AggregatedResults::new(1);
+        results.started_at = SystemTime::now() - Duration::from_secs(elapsed) - DELIVERY_DELAY;
+        for (second, requests) in requests.iter().enumerate() {
+            results.window_mut(second).total.requests = *requests;
+        }
+        return results;
+    }
+
+    #[test]
+    fn trailing_sample_waits_for_the_whole_window() {
+        let results = results_with_windows(2, &[5, 7, 9]);
+        assert!(results.trailing_sample(&ThresholdScope::Global, 3).is_none());
+        let (sample, seconds) = results.trailing_sample(&ThresholdScope::Global, 2).unwrap();
+        assert_eq!((sample.requests, seconds), (12, 2));
+    }
+
+    #[test]
+    fn trailing_sample_skips_the_current_window() {
+        let results = results_with_windows(3, &[5, 7, 9, 100]);
+        let (sample, seconds) = results.trailing_sample(&ThresholdScope::Global, 2).unwrap();
+        assert_eq!((sample.requests, seconds), (16, 2));
+    }
+
+    #[test]
+    fn windows_are_closed_after_the_delivery_delay() {
+        let mut results = AggregatedResults::new(2);
+        results.started_at = SystemTime::now() - Duration::from_secs(4);
+        assert_eq!(results.closed_windows(), 1);
+        results.started_at = SystemTime::now() - Duration::from_secs(4) - DELIVERY_DELAY;
+        assert_eq!(results.closed_windows(), 2);
+    }
+
+    #[test]
+    fn window_samples_follow_the_scope() {
+        let mut results = AggregatedResults::new(1);
+        let started_at = results.started_at;
+        let request = |scenario: &str, id: &str| {
+            let mut result = TaskResult::request(scenario, id, 10);
+            result.started_at = started_at;
+            return result;
+        };
+        results.add(ReportMessage {
+            current_users: 1,
+            results: vec![Ok(vec![request("main", "GET /a"), request("main", "GET /b"), request("admin", "GET /a")])],
+            duration: 0,
+        });
+
+        let window = &results.windows[0];
+        assert_eq!(window.sample(&ThresholdScope::Global).requests, 3);
+        assert_eq!(window.sample(&ThresholdScope::Request("GET /a".to_string())).requests, 2);
+        assert_eq!(window.sample(&ThresholdScope::Scenario("main".to_string())).requests, 2);
+    }
+}
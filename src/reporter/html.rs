@@ -0,0 +1,182 @@
+use std::fs;
+
+use super::{Reporter, AggregatedResults, UrlResults, GroupResults, TimeWindow, error_rate, escape_xml, mean, percentile};
+
+const CHART_WIDTH: f64 = 860.0;
+const CHART_HEIGHT: f64 = 220.0;
+const CHART_PADDING: f64 = 40.0;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 24px; color: #222; }
+table { border-collapse: collapse; margin-bottom: 24px; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+th { background: #f0f0f0; }
+.chart { margin-bottom: 24px; }
+.legend span { margin-right: 16px; }";
+
+struct Line<'a> {
+    name: &'a str,
+    color: &'a str,
+    values: Vec<f64>,
+}
+
+// inline svg, so the report is a single file without scripts
+fn line_chart(title: &str, unit: &str, times: &[u64], lines: &[Line]) -> String {
+    let max_time = times.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let max_value = lines.iter()
+        .flat_map(|line| line.values.iter().copied())
+        .fold(0.0, f64::max)
+        .max(1.0);
+    let plot_width = CHART_WIDTH - 2.0 * CHART_PADDING;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_PADDING;
+    let x = |time: u64| CHART_PADDING + time as f64 / max_time * plot_width;
+    let y = |value: f64| CHART_HEIGHT - CHART_PADDING - value / max_value * plot_height;
+
+    let mut svg = format!("<div class=\"chart\"><h3>{}</h3>", escape_xml(title));
+    svg += &format!("<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">", CHART_WIDTH, CHART_HEIGHT);
+    svg += &format!(
+        "<line x1=\"{p}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/><line x1=\"{p}\" y1=\"{p}\" x2=\"{p}\" y2=\"{b}\" stroke=\"#999\"/>",
+        p = CHART_PADDING, b = CHART_HEIGHT - CHART_PADDING, r = CHART_WIDTH - CHART_PADDING,
+    );
+    svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{:.0}{}</text>", CHART_PADDING - 4.0, CHART_PADDING + 4.0, max_value, unit);
+    svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">0</text>", CHART_PADDING - 4.0, CHART_HEIGHT - CHART_PADDING);
+    svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{}s</text>", CHART_WIDTH - CHART_PADDING, CHART_HEIGHT - CHART_PADDING + 16.0, max_time);
+
+    for line in lines {
+        let points: Vec<String> = times.iter().zip(line.values.iter())
+            .map(|(time, value)| format!("{:.1},{:.1}", x(*time), y(*value)))
+            .collect();
+        svg += &format!("<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>", line.color, points.join(" "));
+    }
+    svg += "</svg><div class=\"legend\">";
+    for line in lines {
+        svg += &format!("<span style=\"color: {}\">&#9632; {}</span>", line.color, escape_xml(line.name));
+    }
+    svg += "</div></div>";
+    return svg;
+}
+
+fn request_row(id: &str, url_results: &UrlResults, duration: usize) -> String {
+    let durations = &url_results.durations;
+    return format!(
+        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.2}%</td><td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        escape_xml(id),
+        url_results.num_of_requests,
+        url_results.num_of_errors,
+        error_rate(url_results.num_of_errors, url_results.num_of_requests),
+        if duration == 0 { 0.0 } else { url_results.num_of_requests as f64 / duration as f64 },
+        mean(durations),
+        percentile(durations, 50.0),
+        percentile(durations, 95.0),
+        percentile(durations, 99.0),
+        durations.keys().max().copied().unwrap_or(0),
+    );
+}
+
+fn group_row(name: &str, group_results: &GroupResults) -> String {
+    let durations = &group_results.durations;
+    return format!(
+        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.2}%</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        escape_xml(name),
+        group_results.num_of_runs,
+        group_results.num_of_errors,
+        error_rate(group_results.num_of_errors, group_results.num_of_runs),
+        mean(durations),
+        percentile(durations, 50.0),
+        percentile(durations, 95.0),
+        durations.keys().max().copied().unwrap_or(0),
+    );
+}
+
+pub struct HtmlReporter {
+    path: String,
+}
+
+impl HtmlReporter {
+    pub fn new(path: &str) -> HtmlReporter {
+        return HtmlReporter {
+            path: path.to_string(),
+        };
+    }
+
+    fn charts(&self, results: &AggregatedResults) -> String {
+        let windows = &results.windows;
+        let times: Vec<u64> = windows.iter().map(|window| window.start as u64).collect();
+        let values = |value: fn(&TimeWindow) -> f64| windows.iter().map(value).collect::<Vec<f64>>();
+
+        let mut html = String::new();
+        html += &line_chart("Requests per second", "", &times, &[
+            Line { name: "rps", color: "#1f77b4", values: values(|window| window.rps()) },
+        ]);
+        html += &line_chart("Active users", "", &times, &[
+            Line { name: "users", color: "#2ca02c", values: values(|window| window.users as f64) },
+        ]);
+        html += &line_chart("Latency percentiles", "ms", &times, &[
+            Line { name: "p50", color: "#1f77b4", values: values(|window| percentile(&window.total.durations, 50.0) as f64) },
+            Line { name: "p95", color: "#ff7f0e", values: values(|window| percentile(&window.total.durations, 95.0) as f64) },
+            Line { name: "p99", color: "#d62728", values: values(|window| percentile(&window.total.durations, 99.0) as f64) },
+        ]);
+        html += &line_chart("Error rate", "%", &times, &[
+            Line { name: "errors", color: "#d62728", values: values(|window| window.error_rate()) },
+        ]);
+        return html;
+    }
+
+    fn tables(&self, results: &AggregatedResults) -> String {
+        let mut html = String::new();
+        let mut scenario_names: Vec<&String> = results.scenario_results.keys().collect();
+        scenario_names.sort();
+
+        for name in scenario_names {
+            let scenario_results = &results.scenario_results[name];
+            html += &format!("<h2>Scenario: {}</h2>", escape_xml(name));
+            html += &format!("<p>Requests: {}, errors: {} ({:.2}%)</p>",
+                scenario_results.num_of_requests,
+                scenario_results.num_of_errors,
+                error_rate(scenario_results.num_of_errors, scenario_results.num_of_requests),
+            );
+
+            html += "<table><tr><th>Request</th><th>Requests</th><th>Errors</th><th>Error rate</th><th>RPS</th>\
+                <th>Mean (ms)</th><th>p50</th><th>p95</th><th>p99</th><th>Max</th></tr>";
+            let mut ids: Vec<&String> = scenario_results.url_results.keys().collect();
+            ids.sort();
+            for id in ids {
+                html += &request_row(id, &scenario_results.url_results[id], results.duration);
+            }
+            html += "</table>";
+
+            if !scenario_results.group_results.is_empty() {
+                html += "<table><tr><th>Group</th><th>Runs</th><th>Errors</th><th>Error rate</th>\
+                    <th>Mean (ms)</th><th>p50</th><th>p95</th><th>Max</th></tr>";
+                let mut group_names: Vec<&String> = scenario_results.group_results.keys().collect();
+                group_names.sort();
+                for group_name in group_names {
+                    html += &group_row(group_name, &scenario_results.group_results[group_name]);
+                }
+                html += "</table>";
+            }
+        }
+        return html;
+    }
+
+    fn report(&self, results: &AggregatedResults) -> String {
+        let mut html = String::from("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Swarm report</title>");
+        html += &format!("<style>{}</style></head><body>", STYLE);
+        html += "<h1>Swarm report</h1>";
+        html += &format!("<p>Duration: {}s, failed users: {}, charts in {}s windows</p>", results.duration, results.num_of_failed_users, results.window_size);
+        if let Some(reason) = &results.abort_reason {
+            html += &format!("<p><strong>Aborted:</strong> {}</p>", escape_xml(reason));
+        }
+        html += &self.charts(results);
+        html += &self.tables(results);
+        html += "</body></html>\n";
+        return html;
+    }
+}
+
+impl Reporter for HtmlReporter {
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        return fs::write(&self.path, self.report(results))
+            .map_err(|err| format!("Could not write HTML report to {}: {}", self.path, err));
+    }
+}
@@ -0,0 +1,110 @@
+use tokio::sync::mpsc::Receiver;
+use tokio::time::{Duration, interval, timeout};
+use tokio::task::JoinHandle;
+use crate::config::{Config, ReporterConfig};
+use crate::runner::{ReportMessage, RunnerStopper};
+use super::{Reporter, AggregatedResults, ThresholdTracker, AbortMonitor, CmdReporter, JsonReporter, HtmlReporter, JunitReporter, PrometheusReporter, InfluxReporter, StatsdReporter, OtlpReporter, ResultsReporter};
+
+fn create_reporter(config: &ReporterConfig) -> Result<Box<dyn Reporter>, String> {
+    return match config {
+        ReporterConfig::Terminal => Ok(Box::new(CmdReporter::new())),
+        ReporterConfig::Json { path } => Ok(Box::new(JsonReporter::new(path))),
+        ReporterConfig::Html { path } => Ok(Box::new(HtmlReporter::new(path))),
+        ReporterConfig::Junit { path } => Ok(Box::new(JunitReporter::new(path))),
+        ReporterConfig::Prometheus { address } => Ok(Box::new(PrometheusReporter::new(*address)?)),
+        ReporterConfig::Influx { target, token, push } => Ok(Box::new(InfluxReporter::new(target, token, push)?)),
+        ReporterConfig::Statsd { address, prefix, dogstatsd, push } => Ok(Box::new(StatsdReporter::new(*address, prefix, *dogstatsd, push)?)),
+        ReporterConfig::Otlp { url, push } => Ok(Box::new(OtlpReporter::new(url, push)?)),
+        ReporterConfig::Results { path, format } => Ok(Box::new(ResultsReporter::new(path, format.clone())?)),
+    };
+}
+
+// the end of the run waits that long for every reporter still pushing metrics
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// a failing reporter must not stop the others, its errors are shown by the terminal report and at the end,
+// printing them here would break the redrawn terminal report
+fn report_error(results: &mut AggregatedResults, result: Result<(), String>) {
+    if let Err(err) = result {
+        results.add_reporter_error(err);
+    }
+}
+
+async fn fan_out(config: Config, mut reporters: Vec<Box<dyn Reporter>>, mut thresholds: ThresholdTracker, mut report_receiver: Receiver<ReportMessage>, stopper: RunnerStopper) -> AggregatedResults {
+    let mut aggregated_results = AggregatedResults::new(config.window_size);
+    let mut abort_monitor = AbortMonitor::new(&config.abort_conditions, stopper);
+
+    for reporter in reporters.iter_mut() {
+        report_error(&mut aggregated_results, reporter.on_start(&config));
+    }
+
+    let mut interval = interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                aggregated_results.abort_reason = abort_monitor.tick(&aggregated_results);
+                aggregated_results.thresholds = thresholds.check(&aggregated_results, false);
+                for reporter in reporters.iter_mut() {
+                    let result = reporter.on_interval(&aggregated_results);
+                    report_error(&mut aggregated_results, result);
+                }
+            },
+            msg = report_receiver.recv() => {
+                match msg {
+                    Some(report_msg) => {
+                        for reporter in reporters.iter_mut() {
+                            report_error(&mut aggregated_results, reporter.on_message(&report_msg));
+                        }
+                        aggregated_results.add(report_msg);
+                    },
+                    None => {
+                        aggregated_results.thresholds = thresholds.check(&aggregated_results, true);
+                        for reporter in reporters.iter_mut() {
+                            let result = reporter.on_finish(&aggregated_results);
+                            report_error(&mut aggregated_results, result);
+                        }
+                        for reporter in reporters.iter_mut() {
+                            if let Some(pending) = reporter.close() {
+                                let result = timeout(CLOSE_TIMEOUT, pending.wait()).await
+                                    .unwrap_or_else(|_| Err(format!("Pushes still queued after {}s were dropped", CLOSE_TIMEOUT.as_secs())));
+                                report_error(&mut aggregated_results, result);
+                            }
+                        }
+                        return aggregated_results;
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct ReporterHub {
+    handle: Option<JoinHandle<AggregatedResults>>,
+}
+
+impl ReporterHub {
+    // the stopper ends the run early when an abort condition is crossed
+    pub fn start(config: Config, runner_receiver: Receiver<ReportMessage>, stopper: RunnerStopper) -> Result<ReporterHub, String> {
+        let reporters = config.reporters.iter()
+            .map(create_reporter)
+            .collect::<Result<Vec<Box<dyn Reporter>>, String>>()?;
+        let thresholds = ThresholdTracker::new(&config.thresholds)?;
+
+        let handle = tokio::spawn(async move {
+            return fan_out(config, reporters, thresholds, runner_receiver, stopper).await;
+        });
+
+        return Ok(ReporterHub {
+            handle: Some(handle),
+        });
+    }
+
+    // returns the final results, including thresholds and the abort reason
+    pub async fn wait_until_finished(&mut self) -> AggregatedResults {
+        if let Some(handle) = self.handle.take() {
+            return handle.await.unwrap();
+        }
+        return AggregatedResults::new(1);
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::SystemTime;
+
+use crate::config::{PushConfig, PushTarget};
+use crate::runner::{ReportMessage, TaskKind, TaskResult};
+use super::{Reporter, AggregatedResults};
+use super::push::{PushSink, LineBuffer, PendingPush, unix_nanos};
+
+fn escape_tag(value: &str) -> String {
+    return value.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+        .replace('\n', "\\n");
+}
+
+// empty tag values are not allowed by the line protocol, so they are left out
+fn tags(pairs: &[(&str, String)]) -> String {
+    return pairs.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!(",{}={}", key, escape_tag(value)))
+        .collect();
+}
+
+fn request_line(result: &TaskResult) -> String {
+    let measurement = match result.kind {
+        TaskKind::Request => "swarm_request",
+        TaskKind::Group => "swarm_group",
+    };
+    let tags = tags(&[
+        ("scenario", result.scenario.clone()),
+        ("name", result.id.clone()),
+        ("group", result.group.clone().unwrap_or_default()),
+        ("status", result.status.map(|status| status.to_string()).unwrap_or_default()),
+        ("error_type", if result.error { result.error_type.as_str().to_string() } else { String::new() }),
+    ]);
+
+    let mut fields = format!("duration={}i,success={}", result.duration, result.success);
+    if result.kind == TaskKind::Request {
+        fields += &format!(",attempt={}i,bytes_received={}i,throttled={}i", result.attempt, result.bytes_received, result.throttled);
+        if let Some(time_to_first_byte) = result.time_to_first_byte {
+            fields += &format!(",time_to_first_byte={}i", time_to_first_byte);
+        }
+    }
+    return format!("{}{} {} {}", measurement, tags, fields, unix_nanos(result.started_at));
+}
+
+// Pushes every task result as a point, plus the number of active users on every flush.
+pub struct InfluxReporter {
+    buffer: LineBuffer,
+}
+
+impl InfluxReporter {
+    pub fn new(target: &PushTarget, token: &Option<String>, push: &PushConfig) -> Result<InfluxReporter, String> {
+        let mut headers = vec![];
+        if let Some(token) = token {
+            headers.push(("authorization".to_string(), format!("Token {}", token)));
+        }
+        let sink = PushSink::new(target, "text/plain; charset=utf-8", headers)?;
+        return Ok(InfluxReporter {
+            buffer: LineBuffer::new(sink, push),
+        });
+    }
+
+    fn record_users(&mut self, results: &AggregatedResults) {
+        self.buffer.push(format!("swarm_users value={}i,failed={}i {}",
+            results.current_users, results.num_of_failed_users, unix_nanos(SystemTime::now())));
+    }
+}
+
+impl Reporter for InfluxReporter {
+    fn on_message(&mut self, message: &ReportMessage) -> Result<(), String> {
+        for results in message.results.iter().flatten() {
+            for result in results.iter() {
+                self.buffer.push(request_line(result));
+            }
+        }
+        return Ok(());
+    }
+
+    fn on_interval(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        if self.buffer.is_due() {
+            self.record_users(results);
+            return self.buffer.flush();
+        }
+        return Ok(());
+    }
+
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        self.record_users(results);
+        return self.buffer.flush();
+    }
+
+    fn close(&mut self) -> Option<PendingPush> {
+        return self.buffer.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::runner::ErrorType;
+
+    #[test]
+    fn tag_separators_are_escaped() {
+        assert_eq!(escape_tag("GET /users"), "GET\\ /users");
+        assert_eq!(escape_tag("a,b=c\\d\ne"), "a\\,b\\=c\\\\d\\ne");
+    }
+
+    #[test]
+    fn empty_tags_are_left_out() {
+        assert_eq!(tags(&[("scenario", "main".to_string()), ("group", String::new()), ("status", "200".to_string())]), ",scenario=main,status=200");
+    }
+
+    #[test]
+    fn requests_are_points_with_nanosecond_timestamps() {
+        let mut result = TaskResult::request("main", "GET /users", 12);
+        result.started_at = UNIX_EPOCH + Duration::from_secs(2);
+        result.bytes_received = 64;
+        assert_eq!(request_line(&result),
+            "swarm_request,scenario=main,name=GET\\ /users,status=200 duration=12i,success=true,attempt=1i,bytes_received=64i,throttled=0i,time_to_first_byte=12i 2000000000");
+
+        let mut result = result.failed(None, ErrorType::Timeout);
+        result.group = Some("checkout".to_string());
+        result.time_to_first_byte = None;
+        assert_eq!(request_line(&result),
+            "swarm_request,scenario=main,name=GET\\ /users,group=checkout,error_type=timeout duration=12i,success=false,attempt=1i,bytes_received=64i,throttled=0i 2000000000");
+    }
+
+    #[test]
+    fn groups_only_have_durations() {
+        let mut result = TaskResult::request("main", "checkout", 120);
+        result.kind = TaskKind::Group;
+        result.status = None;
+        result.started_at = UNIX_EPOCH;
+        assert_eq!(request_line(&result), "swarm_group,scenario=main,name=checkout duration=120i,success=true 0");
+    }
+}
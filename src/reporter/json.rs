@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::fs;
+use std::time::SystemTime;
+
+use serde_json::{json, Map, Value};
+use crate::config::Config;
+use crate::runner::ErrorType;
+use super::{Reporter, AggregatedResults, UrlResults, GroupResults, TimeWindow, mean, percentile, unix_millis};
+
+// bump on every change of the report layout, see docs/json_report.md
+const SCHEMA_VERSION: usize = 4;
+
+fn per_second(count: usize, duration: usize) -> f64 {
+    if duration == 0 {
+        return 0.0;
+    }
+    return count as f64 / duration as f64;
+}
+
+fn error_types_json(error_types: &HashMap<ErrorType, usize>) -> Value {
+    let mut errors = Map::new();
+    for (error_type, counter) in error_types.iter() {
+        errors.insert(error_type.as_str().to_string(), json!(counter));
+    }
+    return Value::Object(errors);
+}
+
+fn durations_json(durations: &HashMap<usize, usize>) -> Value {
+    return json!({
+        "min": durations.keys().min().copied().unwrap_or(0),
+        "mean": mean(durations),
+        "p50": percentile(durations, 50.0),
+        "p90": percentile(durations, 90.0),
+        "p95": percentile(durations, 95.0),
+        "p99": percentile(durations, 99.0),
+        "max": durations.keys().max().copied().unwrap_or(0),
+    });
+}
+
+// a scenario has the durations and error types of all its requests
+fn merge_counters<K: Clone + Eq + Hash>(merged: &mut HashMap<K, usize>, counters: &HashMap<K, usize>) {
+    for (key, counter) in counters.iter() {
+        *merged.entry(key.clone()).or_insert(0) += counter;
+    }
+}
+
+fn request_json(id: &str, url_results: &UrlResults, duration: usize) -> Value {
+    return json!({
+        "id": id,
+        "requests": url_results.num_of_requests,
+        "errors": url_results.num_of_errors,
+        "retried": url_results.num_of_retried,
+        "error_types": error_types_json(&url_results.error_types),
+        "throughput": per_second(url_results.num_of_requests, duration),
+        "durations": durations_json(&url_results.durations),
+        "http_versions": url_results.http_versions,
+        "connections": {
+            "new": url_results.new_connections,
+            "reused": url_results.reused_connections,
+        },
+        "bytes": {
+            "received": url_results.bytes_received,
+            "decoded": url_results.bytes_decoded,
+        },
+        "rate_limited": {
+            "requests": url_results.num_of_throttled,
+            "wait": url_results.throttled_time,
+        },
+    });
+}
+
+fn group_json(name: &str, group_results: &GroupResults) -> Value {
+    let mut requests: Vec<&String> = group_results.requests.iter().collect();
+    requests.sort();
+
+    return json!({
+        "name": name,
+        "parent": group_results.parent,
+        "runs": group_results.num_of_runs,
+        "errors": group_results.num_of_errors,
+        "error_types": error_types_json(&group_results.error_types),
+        "durations": durations_json(&group_results.durations),
+        "requests": requests,
+    });
+}
+
+fn config_json(config: &Config) -> Value {
+    let scenarios: Vec<Value> = config.scenarios.iter()
+        .map(|scenario| json!({
+            "name": scenario.name,
+            "weight": scenario.weight,
+            "workload": scenario.workload.as_ref().map(|workload| format!("{:?}", workload)),
+            "start_offset": scenario.start_offset,
+        }))
+        .collect();
+
+    return json!({
+        "workload": format!("{:?}", config.workload),
+        "scenarios": scenarios,
+        "http_version": format!("{:?}", config.http.http_version),
+    });
+}
+
+fn window_json(window: &TimeWindow) -> Value {
+    return json!({
+        "time": window.start,
+        "users": window.users,
+        "requests": window.total.requests,
+        "errors": window.total.errors,
+        "throughput": window.rps(),
+        "p50": percentile(&window.total.durations, 50.0),
+        "p95": percentile(&window.total.durations, 95.0),
+        "p99": percentile(&window.total.durations, 99.0),
+    });
+}
+
+pub struct JsonReporter {
+    path: String,
+    config: Value,
+    started_at: SystemTime,
+}
+
+impl JsonReporter {
+    pub fn new(path: &str) -> JsonReporter {
+        return JsonReporter {
+            path: path.to_string(),
+            config: Value::Null,
+            started_at: SystemTime::now(),
+        };
+    }
+
+    fn report(&self, results: &AggregatedResults, finished_at: SystemTime) -> Value {
+        let duration = results.duration;
+        let mut total_requests = 0;
+        let mut total_errors = 0;
+
+        let mut scenario_names: Vec<&String> = results.scenario_results.keys().collect();
+        scenario_names.sort();
+
+        let mut scenarios = vec![];
+        for name in scenario_names {
+            let scenario_results = &results.scenario_results[name];
+            total_requests += scenario_results.num_of_requests;
+            total_errors += scenario_results.num_of_errors;
+
+            let mut ids: Vec<&String> = scenario_results.url_results.keys().collect();
+            ids.sort();
+            let requests: Vec<Value> = ids.into_iter()
+                .map(|id| request_json(id, &scenario_results.url_results[id], duration))
+                .collect();
+
+            let mut group_names: Vec<&String> = scenario_results.group_results.keys().collect();
+            group_names.sort();
+            let groups: Vec<Value> = group_names.into_iter()
+                .map(|name| group_json(name, &scenario_results.group_results[name]))
+                .collect();
+
+            let mut error_types = HashMap::new();
+            let mut durations = HashMap::new();
+            for url_results in scenario_results.url_results.values() {
+                merge_counters(&mut error_types, &url_results.error_types);
+                merge_counters(&mut durations, &url_results.durations);
+            }
+
+            scenarios.push(json!({
+                "name": name,
+                "requests": scenario_results.num_of_requests,
+                "errors": scenario_results.num_of_errors,
+                "error_types": error_types_json(&error_types),
+                "throughput": per_second(scenario_results.num_of_requests, duration),
+                "durations": durations_json(&durations),
+                "request_stats": requests,
+                "groups": groups,
+            }));
+        }
+
+        let series: Vec<Value> = results.windows.iter().map(window_json).collect();
+
+        return json!({
+            "schema_version": SCHEMA_VERSION,
+            "metadata": {
+                "host": gethostname::gethostname().to_string_lossy(),
+                "started_at": unix_millis(self.started_at),
+                "finished_at": unix_millis(finished_at),
+                "duration": duration,
+                "window_size": results.window_size,
+                "config": self.config,
+            },
+            "summary": {
+                "requests": total_requests,
+                "errors": total_errors,
+                "throughput": per_second(total_requests, duration),
+                "failed_users": results.num_of_failed_users,
+                "abort_reason": results.abort_reason,
+            },
+            "scenarios": scenarios,
+            "time_series": series,
+        });
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn on_start(&mut self, config: &Config) -> Result<(), String> {
+        self.started_at = SystemTime::now();
+        self.config = config_json(config);
+        return Ok(());
+    }
+
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        let report = self.report(results, SystemTime::now());
+        let content = serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?;
+        return fs::write(&self.path, content)
+            .map_err(|err| format!("Could not write JSON report to {}: {}", self.path, err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{ReportMessage, TaskResult};
+
+    #[test]
+    fn scenario_merges_its_requests() {
+        let mut results = AggregatedResults::new(1);
+        results.add(ReportMessage {
+            current_users: 1,
+            results: vec![Ok(vec![
+                TaskResult::request("main", "GET /a", 10),
+                TaskResult::request("main", "GET /b", 30),
+                TaskResult::request("main", "GET /b", 0).failed(Some(503), ErrorType::Request5xx),
+                TaskResult::request("main", "GET /a", 0).failed(None, ErrorType::Timeout),
+                TaskResult::request("main", "GET /b", 0).failed(Some(500), ErrorType::Request5xx),
+            ])],
+            duration: 1,
+        });
+
+        let report = JsonReporter::new("report.json").report(&results, SystemTime::now());
+        let scenario = &report["scenarios"][0];
+        assert_eq!(report["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(scenario["error_types"], json!({ "request_5xx": 2, "timeout": 1 }));
+        assert_eq!(scenario["durations"]["min"], json!(10));
+        assert_eq!(scenario["durations"]["p50"], json!(10));
+        assert_eq!(scenario["durations"]["max"], json!(30));
+        assert_eq!(scenario["durations"]["mean"], json!(20));
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs;
+
+use super::{Reporter, AggregatedResults, escape_xml};
+
+// Every threshold becomes a test case, failing with the measured value.
+pub struct JunitReporter {
+    path: String,
+}
+
+impl JunitReporter {
+    pub fn new(path: &str) -> JunitReporter {
+        return JunitReporter {
+            path: path.to_string(),
+        };
+    }
+
+    fn report(&self, results: &AggregatedResults) -> String {
+        let threshold_results = &results.thresholds;
+        let mut failures = threshold_results.iter().filter(|result| !result.passed).count();
+        let mut tests = threshold_results.len();
+        if results.abort_reason.is_some() {
+            failures += 1;
+            tests += 1;
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml += &format!("<testsuites tests=\"{}\" failures=\"{}\" time=\"{}\">\n", tests, failures, results.duration);
+        xml += &format!("  <testsuite name=\"swarm\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n", tests, failures, results.duration);
+        for result in threshold_results.iter() {
+            match &result.failure {
+                Some(failure) => {
+                    xml += &format!("    <testcase classname=\"thresholds\" name=\"{}\">\n", escape_xml(&result.name));
+                    xml += &format!("      <failure message=\"{}\" type=\"threshold\">{}</failure>\n", escape_xml(failure), escape_xml(failure));
+                    xml += "    </testcase>\n";
+                },
+                None => {
+                    xml += &format!("    <testcase classname=\"thresholds\" name=\"{}\"/>\n", escape_xml(&result.name));
+                },
+            }
+        }
+        if let Some(reason) = &results.abort_reason {
+            xml += "    <testcase classname=\"abort\" name=\"run finished without being aborted\">\n";
+            xml += &format!("      <failure message=\"{}\" type=\"abort\">{}</failure>\n", escape_xml(reason), escape_xml(reason));
+            xml += "    </testcase>\n";
+        }
+        xml += "  </testsuite>\n</testsuites>\n";
+        return xml;
+    }
+}
+
+impl Reporter for JunitReporter {
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        return fs::write(&self.path, self.report(results))
+            .map_err(|err| format!("Could not write JUnit report to {}: {}", self.path, err));
+    }
+}
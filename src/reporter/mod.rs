@@ -1,5 +1,30 @@
 mod reporter;
+mod aggregate;
+mod hub;
+mod thresholds;
+mod abort;
 mod cmd;
+mod json;
+mod html;
+mod junit;
+mod prometheus;
+mod push;
+mod influx;
+mod statsd;
+mod otlp;
+mod results;
 
-pub use reporter::Reporter;
-pub use cmd::CmdReporter;
\ No newline at end of file
+pub use reporter::*;
+pub use aggregate::*;
+pub use hub::*;
+pub use thresholds::*;
+pub use abort::*;
+pub use cmd::CmdReporter;
+pub use json::JsonReporter;
+pub use html::HtmlReporter;
+pub use junit::JunitReporter;
+pub use prometheus::PrometheusReporter;
+pub use influx::InfluxReporter;
+pub use statsd::StatsdReporter;
+pub use otlp::OtlpReporter;
+pub use results::ResultsReporter;
\ No newline at end of file
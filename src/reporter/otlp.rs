@@ -0,0 +1,202 @@
+use std::time::{Instant, SystemTime};
+
+use serde_json::{json, Value};
+use tokio::time::Duration;
+use crate::config::{Config, PushConfig};
+use crate::runner::ReportMessage;
+use super::{Reporter, AggregatedResults};
+use super::prometheus::{Metrics, DURATION_BUCKETS};
+use super::push::{PushSink, PendingPush, unix_nanos};
+
+// the OTLP json encoding, see opentelemetry-proto, uses strings for 64 bit integers
+fn int_value(value: u64) -> Value {
+    return json!(value.to_string());
+}
+
+fn attributes(pairs: &[(&str, &str)]) -> Value {
+    let attributes: Vec<Value> = pairs.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect();
+    return json!(attributes);
+}
+
+const CUMULATIVE: u8 = 2;
+
+// Pushes the totals since the start of the run as cumulative OTLP metrics, one request per flush.
+pub struct OtlpReporter {
+    sink: PushSink,
+    metrics: Metrics,
+    started_at: SystemTime,
+    flush_interval: Duration,
+    last_flush: Instant,
+}
+
+impl OtlpReporter {
+    pub fn new(url: &str, push: &PushConfig) -> Result<OtlpReporter, String> {
+        return Ok(OtlpReporter {
+            sink: PushSink::http(url, "application/json", vec![])?,
+            metrics: Metrics::new(),
+            started_at: SystemTime::now(),
+            flush_interval: Duration::from_secs(push.flush_interval.try_into().unwrap()),
+            last_flush: Instant::now(),
+        });
+    }
+
+    fn payload(&self, results: &AggregatedResults) -> Value {
+        let start = int_value(unix_nanos(self.started_at));
+        let now = int_value(unix_nanos(SystemTime::now()));
+
+        let requests: Vec<Value> = self.metrics.requests.iter()
+            .map(|(labels, counter)| json!({
+                "attributes": attributes(&[
+                    ("scenario", &labels.scenario),
+                    ("name", &labels.name),
+                    ("status", &labels.status),
+                    ("error_type", &labels.error_type),
+                ]),
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "asInt": int_value(*counter),
+            }))
+            .collect();
+
+        let durations: Vec<Value> = self.metrics.durations.iter()
+            .map(|(labels, histogram)| {
+                // the last bucket counts the values above the highest bound
+                let mut bucket_counts: Vec<Value> = histogram.buckets.iter().map(|counter| int_value(*counter)).collect();
+                bucket_counts.push(int_value(histogram.count - histogram.buckets.iter().sum::<u64>()));
+                return json!({
+                    "attributes": attributes(&[("scenario", &labels.scenario), ("name", &labels.name)]),
+                    "startTimeUnixNano": start,
+                    "timeUnixNano": now,
+                    "count": int_value(histogram.count),
+                    "sum": histogram.sum,
+                    "bucketCounts": bucket_counts,
+                    "explicitBounds": DURATION_BUCKETS,
+                });
+            })
+            .collect();
+
+        let host = gethostname::gethostname().to_string_lossy().to_string();
+        return json!({
+            "resourceMetrics": [{
+                "resource": {
+                    "attributes": attributes(&[("service.name", "swarm"), ("host.name", &host)]),
+                },
+                "scopeMetrics": [{
+                    "scope": { "name": "swarm" },
+                    "metrics": [
+                        {
+                            "name": "swarm.requests",
+                            "description": "Requests sent, by status and error type.",
+                            "unit": "{request}",
+                            "sum": { "aggregationTemporality": CUMULATIVE, "isMonotonic": true, "dataPoints": requests },
+                        },
+                        {
+                            "name": "swarm.request.duration",
+                            "description": "Request durations.",
+                            "unit": "s",
+                            "histogram": { "aggregationTemporality": CUMULATIVE, "dataPoints": durations },
+                        },
+                        {
+                            "name": "swarm.users",
+                            "description": "Users currently running.",
+                            "unit": "{user}",
+                            "gauge": { "dataPoints": [{ "timeUnixNano": now, "asInt": int_value(results.current_users as u64) }] },
+                        },
+                        {
+                            "name": "swarm.users.failed",
+                            "description": "Users that failed to run their scenario.",
+                            "unit": "{user}",
+                            "sum": {
+                                "aggregationTemporality": CUMULATIVE,
+                                "isMonotonic": true,
+                                "dataPoints": [{ "startTimeUnixNano": start, "timeUnixNano": now, "asInt": int_value(self.metrics.failed_users) }],
+                            },
+                        },
+                    ],
+                }],
+            }],
+        });
+    }
+
+    fn flush(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        self.last_flush = Instant::now();
+        self.sink.send(self.payload(results).to_string().into_bytes())?;
+        return self.sink.failures();
+    }
+}
+
+impl Reporter for OtlpReporter {
+    fn on_start(&mut self, _config: &Config) -> Result<(), String> {
+        self.started_at = SystemTime::now();
+        return Ok(());
+    }
+
+    fn on_message(&mut self, message: &ReportMessage) -> Result<(), String> {
+        self.metrics.add(message);
+        return Ok(());
+    }
+
+    fn on_interval(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        if self.last_flush.elapsed() >= self.flush_interval {
+            return self.flush(results);
+        }
+        return Ok(());
+    }
+
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        return self.flush(results);
+    }
+
+    fn close(&mut self) -> Option<PendingPush> {
+        return self.sink.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{ErrorType, TaskResult};
+
+    #[test]
+    fn integers_are_strings() {
+        assert_eq!(int_value(u64::MAX), json!("18446744073709551615"));
+    }
+
+    #[test]
+    fn empty_attributes_are_left_out() {
+        assert_eq!(attributes(&[("scenario", "main"), ("status", "")]),
+            json!([{ "key": "scenario", "value": { "stringValue": "main" } }]));
+    }
+
+    #[tokio::test]
+    async fn payload_has_cumulative_metrics() {
+        let mut reporter = OtlpReporter::new("http://127.0.0.1:4318/v1/metrics", &PushConfig::new()).unwrap();
+        let failed = TaskResult::request("main", "GET /users", 0).failed(None, ErrorType::Timeout);
+        reporter.on_message(&ReportMessage {
+            current_users: 2,
+            results: vec![Ok(vec![TaskResult::request("main", "GET /users", 20), TaskResult::request("main", "GET /users", 20_000), failed])],
+            duration: 1,
+        }).unwrap();
+        let mut results = AggregatedResults::new(1);
+        results.current_users = 2;
+
+        let payload = reporter.payload(&results);
+        let metrics = &payload["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics[0]["name"], json!("swarm.requests"));
+        let requests = &metrics[0]["sum"]["dataPoints"];
+        assert_eq!(requests[0]["attributes"], attributes(&[("scenario", "main"), ("name", "GET /users"), ("error_type", "timeout")]));
+        assert_eq!(requests[0]["asInt"], json!("1"));
+        assert_eq!(requests[1]["attributes"], attributes(&[("scenario", "main"), ("name", "GET /users"), ("status", "200")]));
+        assert_eq!(requests[1]["asInt"], json!("2"));
+
+        let durations = &metrics[1]["histogram"]["dataPoints"][0];
+        assert_eq!(durations["count"], json!("3"));
+        // 0ms and 20ms in their buckets, 20s above the highest bound
+        assert_eq!(durations["bucketCounts"], json!(["1", "0", "1", "0", "0", "0", "0", "0", "0", "0", "0", "1"]));
+        assert_eq!(metrics[2]["gauge"]["dataPoints"][0]["asInt"], json!("2"));
+        assert!(reporter.close().is_some());
+    }
+}
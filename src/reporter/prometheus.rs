@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::{Body, Request, Response, Server, StatusCode};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use crate::runner::{ReportMessage, TaskKind};
+use super::{Reporter, AggregatedResults};
+
+// upper bounds in seconds
+pub const DURATION_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RequestLabels {
+    pub scenario: String,
+    pub name: String,
+    pub status: String,
+    pub error_type: String,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DurationLabels {
+    pub scenario: String,
+    pub name: String,
+}
+
+pub struct Histogram {
+    // counts per bucket, cumulative counts are computed when rendering
+    pub buckets: [u64; DURATION_BUCKETS.len()],
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        return Histogram {
+            buckets: [0; DURATION_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        };
+    }
+
+    fn observe(&mut self, seconds: f64) {
+        if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[index] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+// Totals since the start of the run, shared with the OTLP reporter.
+pub struct Metrics {
+    pub active_users: usize,
+    pub failed_users: u64,
+    pub requests: BTreeMap<RequestLabels, u64>,
+    pub durations: BTreeMap<DurationLabels, Histogram>,
+    pub bytes_received: BTreeMap<DurationLabels, u64>,
+}
+
+fn escape_label(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        return Metrics {
+            active_users: 0,
+            failed_users: 0,
+            requests: BTreeMap::new(),
+            durations: BTreeMap::new(),
+            bytes_received: BTreeMap::new(),
+        };
+    }
+
+    pub fn add(&mut self, message: &ReportMessage) {
+        self.active_users = message.current_users;
+
+        for user_result in message.results.iter() {
+            let results = match user_result {
+                Ok(results) => results,
+                Err(_) => {
+                    self.failed_users += 1;
+                    continue;
+                },
+            };
+
+            for result in results.iter().filter(|result| result.kind == TaskKind::Request) {
+                let request_labels = RequestLabels {
+                    scenario: result.scenario.clone(),
+                    name: result.id.clone(),
+                    status: result.status.map(|status| status.to_string()).unwrap_or_default(),
+                    error_type: if result.error { result.error_type.as_str().to_string() } else { String::new() },
+                };
+                *self.requests.entry(request_labels).or_insert(0) += 1;
+
+                let labels = DurationLabels {
+                    scenario: result.scenario.clone(),
+                    name: result.id.clone(),
+                };
+                self.durations.entry(labels.clone()).or_insert_with(Histogram::new)
+                    .observe(result.duration as f64 / 1000.0);
+                *self.bytes_received.entry(labels).or_insert(0) += result.bytes_received as u64;
+            }
+        }
+    }
+
+    // text exposition format
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        out += "# HELP swarm_active_users Users currently running.\n# TYPE swarm_active_users gauge\n";
+        let _ = writeln!(out, "swarm_active_users {}", self.active_users);
+
+        out += "# HELP swarm_failed_users_total Users that failed to run their scenario.\n# TYPE swarm_failed_users_total counter\n";
+        let _ = writeln!(out, "swarm_failed_users_total {}", self.failed_users);
+
+        out += "# HELP swarm_requests_total Requests sent, by status and error type.\n# TYPE swarm_requests_total counter\n";
+        for (labels, counter) in self.requests.iter() {
+            let _ = writeln!(out, "swarm_requests_total{{scenario=\"{}\",name=\"{}\",status=\"{}\",error_type=\"{}\"}} {}",
+                escape_label(&labels.scenario), escape_label(&labels.name), labels.status, labels.error_type, counter);
+        }
+
+        out += "# HELP swarm_request_duration_seconds Request durations.\n# TYPE swarm_request_duration_seconds histogram\n";
+        for (labels, histogram) in self.durations.iter() {
+            let common = format!("scenario=\"{}\",name=\"{}\"", escape_label(&labels.scenario), escape_label(&labels.name));
+            let mut cumulative = 0;
+            for (bound, counter) in DURATION_BUCKETS.iter().zip(histogram.buckets.iter()) {
+                cumulative += counter;
+                let _ = writeln!(out, "swarm_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", common, bound, cumulative);
+            }
+            let _ = writeln!(out, "swarm_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", common, histogram.count);
+            let _ = writeln!(out, "swarm_request_duration_seconds_sum{{{}}} {}", common, histogram.sum);
+            let _ = writeln!(out, "swarm_request_duration_seconds_count{{{}}} {}", common, histogram.count);
+        }
+
+        out += "# HELP swarm_bytes_received_total Response body bytes as sent by the server.\n# TYPE swarm_bytes_received_total counter\n";
+        for (labels, counter) in self.bytes_received.iter() {
+            let _ = writeln!(out, "swarm_bytes_received_total{{scenario=\"{}\",name=\"{}\"}} {}",
+                escape_label(&labels.scenario), escape_label(&labels.name), counter);
+        }
+
+        return out;
+    }
+}
+
+async fn serve(metrics: Arc<Mutex<Metrics>>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if request.uri().path() != "/metrics" {
+        let mut response = Response::new(Body::from("Not found"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+
+    let body = metrics.lock().unwrap().render();
+    let response = Response::builder()
+        .header("content-type", "text/plain; version=0.0.4")
+        .body(Body::from(body))
+        .unwrap();
+    return Ok(response);
+}
+
+// Serves the metrics on http://<address>/metrics for as long as the process runs.
+pub struct PrometheusReporter {
+    metrics: Arc<Mutex<Metrics>>,
+    // the endpoint fails in the background, the error is returned with the next message
+    failures: UnboundedReceiver<String>,
+}
+
+impl PrometheusReporter {
+    pub fn new(address: SocketAddr) -> Result<PrometheusReporter, String> {
+        let metrics = Arc::new(Mutex::new(Metrics::new()));
+        let incoming = AddrIncoming::bind(&address)
+            .map_err(|err| format!("Could not bind Prometheus endpoint to {}: {}", address, err))?;
+
+        let service_metrics = metrics.clone();
+        let make_service = make_service_fn(move |_| {
+            let metrics = service_metrics.clone();
+            async move {
+                return Ok::<_, Infallible>(service_fn(move |request| serve(metrics.clone(), request)));
+            }
+        });
+        let server = Server::builder(incoming).serve(make_service);
+        let (failure_sender, failures) = unbounded_channel::<String>();
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                let _ = failure_sender.send(format!("Prometheus endpoint error: {}", err));
+            }
+        });
+
+        return Ok(PrometheusReporter { metrics, failures });
+    }
+}
+
+impl Reporter for PrometheusReporter {
+    fn on_message(&mut self, message: &ReportMessage) -> Result<(), String> {
+        self.metrics.lock().unwrap().add(message);
+        return match self.failures.try_recv() {
+            Ok(failure) => Err(failure),
+            Err(_) => Ok(()),
+        };
+    }
+
+    fn on_finish(&mut self, _results: &AggregatedResults) -> Result<(), String> {
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{ErrorType, TaskResult};
+
+    fn metrics(results: Vec<TaskResult>) -> Metrics {
+        let mut metrics = Metrics::new();
+        metrics.add(&ReportMessage { current_users: 3, results: vec![Ok(results), Err("failed".to_string())], duration: 1 });
+        return metrics;
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(escape_label("GET /users"), "GET /users");
+        assert_eq!(escape_label("say \"hi\"\\n\n"), "say \\\"hi\\\"\\\\n\\n");
+    }
+
+    #[test]
+    fn requests_are_counted_by_status_and_error_type() {
+        let failed = TaskResult::request("main", "GET /users", 0).failed(Some(503), ErrorType::Request5xx);
+        let rendered = metrics(vec![TaskResult::request("main", "GET /users", 10), failed.clone(), failed]).render();
+
+        assert!(rendered.contains("swarm_active_users 3\n"));
+        assert!(rendered.contains("swarm_failed_users_total 1\n"));
+        assert!(rendered.contains("swarm_requests_total{scenario=\"main\",name=\"GET /users\",status=\"200\",error_type=\"\"} 1\n"));
+        assert!(rendered.contains("swarm_requests_total{scenario=\"main\",name=\"GET /users\",status=\"503\",error_type=\"request_5xx\"} 2\n"));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut slow = TaskResult::request("main", "GET \"q\"", 20_000);
+        slow.bytes_received = 100;
+        let rendered = metrics(vec![TaskResult::request("main", "GET \"q\"", 5), TaskResult::request("main", "GET \"q\"", 30), slow]).render();
+
+        let labels = "scenario=\"main\",name=\"GET \\\"q\\\"\"";
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_bucket{{{},le=\"10\"}} 2\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_sum{{{}}} 20.035\n", labels)));
+        assert!(rendered.contains(&format!("swarm_request_duration_seconds_count{{{}}} 3\n", labels)));
+        assert!(rendered.contains(&format!("swarm_bytes_received_total{{{}}} 100\n", labels)));
+    }
+
+    #[test]
+    fn groups_are_not_requests() {
+        let mut group = TaskResult::request("main", "login", 10);
+        group.kind = TaskKind::Group;
+        let metrics = metrics(vec![group]);
+        assert!(metrics.requests.is_empty());
+        assert!(metrics.durations.is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use hyper::{Body, Method, Request, Uri};
+use tokio::sync::mpsc::{channel, unbounded_channel, Sender, UnboundedReceiver};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, timeout};
+use crate::config::{HttpConfig, PushConfig, PushTarget};
+use crate::user::HttpClients;
+
+// keeps datagrams below a typical MTU so they are not fragmented
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+// payloads waiting for the push task, the buffers keep their lines while it is full
+const MAX_QUEUED_PAYLOADS: usize = 16;
+
+// lines kept for the next flush while the target can't be reached, the oldest ones are dropped beyond it
+const MAX_PENDING_LINES: usize = 100_000;
+
+// a slow target must not hold up the queue, nor the end of the run
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn unix_nanos(time: SystemTime) -> u64 {
+    return time.duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos() as u64).unwrap_or(0);
+}
+
+enum Transport {
+    Udp {
+        socket: UdpSocket,
+        address: SocketAddr,
+    },
+    // payloads are posted in order by a background task, so flushing never blocks the reporters
+    Http {
+        sender: Option<Sender<Vec<u8>>>,
+        delivery: Option<PendingPush>,
+    },
+}
+
+fn take_failures(failures: &mut UnboundedReceiver<String>) -> Result<(), String> {
+    let mut errors = vec![];
+    while let Ok(error) = failures.try_recv() {
+        errors.push(error);
+    }
+    return match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        count => Err(format!("{} (and {} more failed pushes)", errors[0], count - 1)),
+    };
+}
+
+// The push task of a closed sink, the hub waits for it to deliver the queued payloads at the end of the run.
+pub struct PendingPush {
+    handle: JoinHandle<()>,
+    failures: UnboundedReceiver<String>,
+}
+
+impl PendingPush {
+    pub async fn wait(mut self) -> Result<(), String> {
+        (&mut self.handle).await.map_err(|err| format!("Push task failed: {}", err))?;
+        return take_failures(&mut self.failures);
+    }
+}
+
+// Delivers payloads to a push target, over UDP or as HTTP POST requests.
+pub struct PushSink {
+    transport: Transport,
+}
+
+impl PushSink {
+    pub fn new(target: &PushTarget, content_type: &'static str, headers: Vec<(String, String)>) -> Result<PushSink, String> {
+        return match target {
+            PushTarget::Udp(address) => PushSink::udp(*address),
+            PushTarget::Http(url) => PushSink::http(url, content_type, headers),
+        };
+    }
+
+    pub fn udp(address: SocketAddr) -> Result<PushSink, String> {
+        let local_address = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local_address).map_err(|err| format!("Could not open UDP socket: {}", err))?;
+        socket.set_nonblocking(true).map_err(|err| err.to_string())?;
+        return Ok(PushSink {
+            transport: Transport::Udp { socket, address },
+        });
+    }
+
+    pub fn http(url: &str, content_type: &'static str, headers: Vec<(String, String)>) -> Result<PushSink, String> {
+        let uri: Uri = url.parse().map_err(|err| format!("Invalid push url {}: {}", url, err))?;
+        let client = HttpClients::new(&HttpConfig::new())?.for_user().client;
+        let (sender, mut receiver) = channel::<Vec<u8>>(MAX_QUEUED_PAYLOADS);
+        let (failure_sender, failures) = unbounded_channel::<String>();
+
+        let handle = tokio::spawn(async move {
+            while let Some(payload) = receiver.recv().await {
+                let mut builder = Request::builder()
+                    .method(Method::POST)
+                    .uri(uri.clone())
+                    .header("content-type", content_type);
+                for (name, value) in headers.iter() {
+                    builder = builder.header(name.as_str(), value.as_str());
+                }
+                let request = builder.body(Body::from(payload)).unwrap();
+
+                let failure = match timeout(PUSH_TIMEOUT, client.request(request)).await {
+                    Err(_) => Some(format!("Push to {} timed out after {}s", uri, PUSH_TIMEOUT.as_secs())),
+                    Ok(Ok(response)) => {
+                        let status = response.status();
+                        let body = hyper::body::to_bytes(response.into_body()).await.unwrap_or_default();
+                        if status.is_success() {
+                            None
+                        } else {
+                            Some(format!("Push to {} failed with {}: {}", uri, status, String::from_utf8_lossy(&body)))
+                        }
+                    },
+                    Ok(Err(err)) => Some(format!("Push to {} failed: {}", uri, err)),
+                };
+                if let Some(failure) = failure {
+                    let _ = failure_sender.send(failure);
+                }
+            }
+        });
+
+        return Ok(PushSink {
+            transport: Transport::Http {
+                sender: Some(sender),
+                delivery: Some(PendingPush { handle, failures }),
+            },
+        });
+    }
+
+    pub fn max_payload_size(&self) -> usize {
+        return match self.transport {
+            Transport::Udp { .. } => MAX_DATAGRAM_SIZE,
+            Transport::Http { .. } => usize::MAX,
+        };
+    }
+
+    pub fn send(&self, payload: Vec<u8>) -> Result<(), String> {
+        return match &self.transport {
+            Transport::Udp { socket, address } => socket.send_to(&payload, address)
+                .map(|_| ())
+                .map_err(|err| format!("Push to {} failed: {}", address, err)),
+            Transport::Http { sender: Some(sender), .. } => sender.try_send(payload)
+                .map_err(|err| match err {
+                    TrySendError::Full(_) => "Push queue is full, the target is too slow".to_string(),
+                    TrySendError::Closed(_) => "Push task is not running".to_string(),
+                }),
+            Transport::Http { sender: None, .. } => Err("Push sink is closed".to_string()),
+        };
+    }
+
+    // HTTP deliveries fail in the background, their errors are returned by the next call
+    pub fn failures(&mut self) -> Result<(), String> {
+        if let Transport::Http { delivery: Some(delivery), .. } = &mut self.transport {
+            return take_failures(&mut delivery.failures);
+        }
+        return Ok(());
+    }
+
+    // no more payloads are accepted, the push task delivers the queued ones
+    pub fn close(&mut self) -> Option<PendingPush> {
+        if let Transport::Http { sender, delivery } = &mut self.transport {
+            sender.take();
+            return delivery.take();
+        }
+        return None;
+    }
+}
+
+// Buffers lines between flushes and sends them in batches.
+pub struct LineBuffer {
+    sink: PushSink,
+    lines: Vec<String>,
+    flush_interval: Duration,
+    max_batch_size: usize,
+    last_flush: Instant,
+}
+
+impl LineBuffer {
+    pub fn new(sink: PushSink, config: &PushConfig) -> LineBuffer {
+        return LineBuffer {
+            sink,
+            lines: vec![],
+            flush_interval: Duration::from_secs(config.flush_interval.try_into().unwrap()),
+            max_batch_size: config.max_batch_size.max(1),
+            last_flush: Instant::now(),
+        };
+    }
+
+    pub fn push(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    pub fn is_due(&self) -> bool {
+        return self.last_flush.elapsed() >= self.flush_interval;
+    }
+
+    // lines that could not be sent stay in the buffer for the next flush
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.last_flush = Instant::now();
+        let max_payload_size = self.sink.max_payload_size();
+
+        // lines before `sent` are delivered, the payload holds the ones after them
+        let mut sent = 0;
+        let mut payload: Vec<u8> = vec![];
+        let mut result = Ok(());
+        for (index, line) in self.lines.iter().enumerate() {
+            let lines_in_payload = index - sent;
+            let full = lines_in_payload == self.max_batch_size
+                || payload.len() + line.len() + 1 > max_payload_size;
+            if full && lines_in_payload > 0 {
+                result = self.sink.send(std::mem::take(&mut payload));
+                if result.is_err() {
+                    break;
+                }
+                sent = index;
+            }
+            payload.extend_from_slice(line.as_bytes());
+            payload.push(b'\n');
+        }
+        if result.is_ok() && sent < self.lines.len() {
+            result = self.sink.send(payload);
+            if result.is_ok() {
+                sent = self.lines.len();
+            }
+        }
+        self.lines.drain(..sent);
+
+        let dropped = self.lines.len().saturating_sub(MAX_PENDING_LINES);
+        self.lines.drain(..dropped);
+        result.map_err(|err| match dropped {
+            0 => format!("{} ({} lines not sent)", err, self.lines.len()),
+            _ => format!("{} ({} lines not sent, {} oldest dropped)", err, self.lines.len(), dropped),
+        })?;
+        return self.sink.failures();
+    }
+
+    // called after the last flush, lines still buffered are not sent anymore
+    pub fn close(&mut self) -> Option<PendingPush> {
+        return self.sink.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::Receiver;
+
+    fn buffer(max_batch_size: usize, queued_payloads: usize) -> (LineBuffer, Receiver<Vec<u8>>) {
+        let (sender, receiver) = channel::<Vec<u8>>(queued_payloads);
+        let sink = PushSink {
+            transport: Transport::Http {
+                sender: Some(sender),
+                delivery: None,
+            },
+        };
+        let config = PushConfig { flush_interval: 1, max_batch_size };
+        return (LineBuffer::new(sink, &config), receiver);
+    }
+
+    fn push_lines(buffer: &mut LineBuffer, lines: &[&str]) {
+        for line in lines {
+            buffer.push(line.to_string());
+        }
+    }
+
+    #[test]
+    fn flush_sends_lines_in_batches() {
+        let (mut buffer, mut receiver) = buffer(2, 10);
+        push_lines(&mut buffer, &["a", "b", "c"]);
+        assert_eq!(buffer.flush(), Ok(()));
+        assert_eq!(receiver.try_recv().unwrap(), b"a\nb\n".to_vec());
+        assert_eq!(receiver.try_recv().unwrap(), b"c\n".to_vec());
+        assert!(buffer.lines.is_empty());
+    }
+
+    #[test]
+    fn flush_keeps_lines_while_the_queue_is_full() {
+        let (mut buffer, mut receiver) = buffer(2, 1);
+        push_lines(&mut buffer, &["a", "b", "c"]);
+        assert_eq!(buffer.flush(), Err("Push queue is full, the target is too slow (1 lines not sent)".to_string()));
+        assert_eq!(buffer.lines, vec!["c"]);
+
+        assert_eq!(receiver.try_recv().unwrap(), b"a\nb\n".to_vec());
+        assert_eq!(buffer.flush(), Ok(()));
+        assert_eq!(receiver.try_recv().unwrap(), b"c\n".to_vec());
+    }
+
+    #[test]
+    fn flush_drops_the_oldest_lines_beyond_the_limit() {
+        let (mut buffer, receiver) = buffer(5000, 1);
+        drop(receiver);
+        for index in 0..MAX_PENDING_LINES + 2 {
+            buffer.push(index.to_string());
+        }
+        let result = buffer.flush();
+        assert_eq!(result, Err(format!("Push task is not running ({} lines not sent, 2 oldest dropped)", MAX_PENDING_LINES)));
+        assert_eq!(buffer.lines.len(), MAX_PENDING_LINES);
+        assert_eq!(buffer.lines[0], "2");
+    }
+
+    #[tokio::test]
+    async fn closed_sinks_report_the_failed_deliveries() {
+        let (failure_sender, failures) = unbounded_channel::<String>();
+        let handle = tokio::spawn(async move {
+            for failure in ["first", "second"] {
+                failure_sender.send(failure.to_string()).unwrap();
+            }
+        });
+        let (sender, _receiver) = channel::<Vec<u8>>(1);
+        let mut sink = PushSink {
+            transport: Transport::Http {
+                sender: Some(sender),
+                delivery: Some(PendingPush { handle, failures }),
+            },
+        };
+
+        let pending = sink.close().unwrap();
+        assert_eq!(sink.send(b"late".to_vec()), Err("Push sink is closed".to_string()));
+        assert_eq!(pending.wait().await, Err("first (and 1 more failed pushes)".to_string()));
+        assert!(sink.close().is_none());
+    }
+}
@@ -1,3 +1,36 @@
-pub trait Reporter {
-    
-}
\ No newline at end of file
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::runner::ReportMessage;
+use super::AggregatedResults;
+use super::push::PendingPush;
+
+// Every reporter gets the same run, driven by the hub:
+// on_start once, on_message for every batch from the runner, on_interval every second
+// with the results aggregated so far, on_finish with the final summary and close at the very end.
+pub trait Reporter: Send {
+    fn on_start(&mut self, _config: &Config) -> Result<(), String> {
+        return Ok(());
+    }
+
+    fn on_message(&mut self, _message: &ReportMessage) -> Result<(), String> {
+        return Ok(());
+    }
+
+    fn on_interval(&mut self, _results: &AggregatedResults) -> Result<(), String> {
+        return Ok(());
+    }
+
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String>;
+
+    // pushes still queued after on_finish, the hub waits for them with a timeout
+    fn close(&mut self) -> Option<PendingPush> {
+        return None;
+    }
+}
+
+// timestamps in exported reports
+pub fn unix_millis(time: SystemTime) -> u64 {
+    let millis = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or(0);
+    return millis.try_into().unwrap();
+}
@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use serde_json::json;
+use crate::config::{Config, ResultsFormat};
+use crate::runner::{ReportMessage, TaskKind, TaskResult};
+use super::{Reporter, AggregatedResults, unix_millis};
+
+// large enough to turn a second worth of results into a few disk writes
+const BUFFER_SIZE: usize = 256 * 1024;
+
+const CSV_HEADER: &str = "timestamp,user_id,scenario,kind,group,id,url,status,success,error_type,attempt,\
+duration,time_to_first_byte,connect_time,tls_time,throttled,bytes_received,bytes_decoded,\
+http_version,reused_connection,remote_addr,local_addr";
+
+fn kind_name(kind: &TaskKind) -> &'static str {
+    return match kind {
+        TaskKind::Request => "request",
+        TaskKind::Group => "group",
+    };
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
+
+fn csv_optional<T: ToString>(value: &Option<T>) -> String {
+    return value.as_ref().map(|value| csv_field(&value.to_string())).unwrap_or_default();
+}
+
+fn csv_line(result: &TaskResult) -> String {
+    let error_type = if result.error { result.error_type.as_str() } else { "" };
+    let fields = [
+        unix_millis(result.started_at).to_string(),
+        result.user_id.to_string(),
+        csv_field(&result.scenario),
+        kind_name(&result.kind).to_string(),
+        csv_optional(&result.group),
+        csv_field(&result.id),
+        csv_field(&result.url),
+        csv_optional(&result.status),
+        result.success.to_string(),
+        error_type.to_string(),
+        result.attempt.to_string(),
+        result.duration.to_string(),
+        csv_optional(&result.time_to_first_byte),
+        csv_optional(&result.connect_time),
+        csv_optional(&result.tls_time),
+        result.throttled.to_string(),
+        result.bytes_received.to_string(),
+        result.bytes_decoded.to_string(),
+        csv_optional(&result.http_version),
+        csv_optional(&result.reused_connection),
+        csv_optional(&result.remote_addr),
+        csv_optional(&result.local_addr),
+    ];
+    return fields.join(",");
+}
+
+fn ndjson_line(result: &TaskResult) -> String {
+    return json!({
+        "timestamp": unix_millis(result.started_at),
+        "user_id": result.user_id,
+        "scenario": result.scenario,
+        "kind": kind_name(&result.kind),
+        "group": result.group,
+        "id": result.id,
+        "url": result.url,
+        "status": result.status,
+        "success": result.success,
+        "error_type": if result.error { Some(result.error_type.as_str()) } else { None },
+        "attempt": result.attempt,
+        "duration": result.duration,
+        "time_to_first_byte": result.time_to_first_byte,
+        "connect_time": result.connect_time,
+        "tls_time": result.tls_time,
+        "throttled": result.throttled,
+        "bytes_received": result.bytes_received,
+        "bytes_decoded": result.bytes_decoded,
+        "http_version": result.http_version,
+        "reused_connection": result.reused_connection,
+        "remote_addr": result.remote_addr,
+        "local_addr": result.local_addr,
+    }).to_string();
+}
+
+enum WriterCommand {
+    Lines(Vec<String>),
+    Flush,
+}
+
+// Runs on its own thread, so disk writes never block the runtime driving the users.
+// Keeps going after an error and returns the first one when the channel closes.
+fn write_lines(path: String, file: File, commands: Receiver<WriterCommand>) -> Result<(), String> {
+    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+    let mut first_error: Option<String> = None;
+    let mut record = |result: std::io::Result<()>| {
+        if let Err(err) = result {
+            first_error.get_or_insert(format!("Could not write results to {}: {}", path, err));
+        }
+    };
+
+    for command in commands {
+        match command {
+            WriterCommand::Lines(lines) => {
+                for line in lines {
+                    record(writeln!(writer, "{}", line));
+                }
+            },
+            WriterCommand::Flush => record(writer.flush()),
+        }
+    }
+    record(writer.flush());
+
+    return match first_error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    };
+}
+
+// Streams every task result to a file, one line per result, flushed once per interval.
+pub struct ResultsReporter {
+    format: ResultsFormat,
+    sender: Option<Sender<WriterCommand>>,
+    writer: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl ResultsReporter {
+    pub fn new(path: &str, format: ResultsFormat) -> Result<ResultsReporter, String> {
+        let file = File::create(path).map_err(|err| format!("Could not create results file {}: {}", path, err))?;
+        let (sender, receiver) = channel();
+        let path = path.to_string();
+        let writer = thread::Builder::new()
+            .name("results-writer".to_string())
+            .spawn(move || write_lines(path, file, receiver))
+            .map_err(|err| format!("Could not start results writer: {}", err))?;
+
+        return Ok(ResultsReporter {
+            format,
+            sender: Some(sender),
+            writer: Some(writer),
+        });
+    }
+
+    fn send(&self, command: WriterCommand) -> Result<(), String> {
+        return match &self.sender {
+            Some(sender) => sender.send(command).map_err(|_| "Results writer has stopped".to_string()),
+            None => Err("Results writer is closed".to_string()),
+        };
+    }
+}
+
+impl Reporter for ResultsReporter {
+    fn on_start(&mut self, _config: &Config) -> Result<(), String> {
+        if let ResultsFormat::Csv = self.format {
+            return self.send(WriterCommand::Lines(vec![CSV_HEADER.to_string()]));
+        }
+        return Ok(());
+    }
+
+    fn on_message(&mut self, message: &ReportMessage) -> Result<(), String> {
+        let mut lines = vec![];
+        for results in message.results.iter().flatten() {
+            for result in results.iter() {
+                lines.push(match self.format {
+                    ResultsFormat::Ndjson => ndjson_line(result),
+                    ResultsFormat::Csv => csv_line(result),
+                });
+            }
+        }
+        if lines.is_empty() {
+            return Ok(());
+        }
+        return self.send(WriterCommand::Lines(lines));
+    }
+
+    fn on_interval(&mut self, _results: &AggregatedResults) -> Result<(), String> {
+        return self.send(WriterCommand::Flush);
+    }
+
+    // the users are done by now, so waiting for the last writes blocks nothing that is measured
+    fn on_finish(&mut self, _results: &AggregatedResults) -> Result<(), String> {
+        self.sender.take();
+        return match self.writer.take() {
+            Some(writer) => writer.join().map_err(|_| "Results writer panicked".to_string())?,
+            None => Ok(()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_fields_are_not_quoted() {
+        assert_eq!(csv_field("GET /users"), "GET /users");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn separators_are_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn quotes_are_doubled() {
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use crate::config::PushConfig;
+use crate::runner::{ReportMessage, TaskKind, TaskResult};
+use super::{Reporter, AggregatedResults};
+use super::push::{PushSink, LineBuffer};
+
+// plain StatsD has no tags, so labels become part of the metric name
+fn metric_segment(value: &str) -> String {
+    return value.chars()
+        .map(|char| if char.is_ascii_alphanumeric() || char == '-' { char } else { '_' })
+        .collect();
+}
+
+// separators of the DogStatsD datagram can't appear in tag values
+fn tag_value(value: &str) -> String {
+    return value.replace([',', '|', '#', '\n'], "_");
+}
+
+// Pushes request timings as they come and request counters summed over the flush interval.
+pub struct StatsdReporter {
+    buffer: LineBuffer,
+    prefix: String,
+    dogstatsd: bool,
+    // (metric name, tags) -> count since the last flush
+    counters: BTreeMap<(String, String), usize>,
+}
+
+impl StatsdReporter {
+    pub fn new(address: SocketAddr, prefix: &str, dogstatsd: bool, push: &PushConfig) -> Result<StatsdReporter, String> {
+        return Ok(StatsdReporter {
+            buffer: LineBuffer::new(PushSink::udp(address)?, push),
+            prefix: prefix.to_string(),
+            dogstatsd,
+            counters: BTreeMap::new(),
+        });
+    }
+
+    // prefix.metric|#tags for DogStatsD, prefix.scenario.name.metric for plain StatsD
+    fn metric(&self, metric: &str, result: &TaskResult, extra_tags: &[(&str, String)]) -> (String, String) {
+        if self.dogstatsd {
+            let mut tags = vec![
+                format!("scenario:{}", tag_value(&result.scenario)),
+                format!("name:{}", tag_value(&result.id)),
+            ];
+            for (key, value) in extra_tags.iter().filter(|(_, value)| !value.is_empty()) {
+                tags.push(format!("{}:{}", key, tag_value(value)));
+            }
+            return (format!("{}.{}", self.prefix, metric), format!("|#{}", tags.join(",")));
+        }
+
+        let mut name = format!("{}.{}.{}.{}", self.prefix, metric_segment(&result.scenario), metric_segment(&result.id), metric);
+        for (_, value) in extra_tags.iter().filter(|(_, value)| !value.is_empty()) {
+            name += &format!(".{}", metric_segment(value));
+        }
+        return (name, String::new());
+    }
+
+    fn record(&mut self, result: &TaskResult) {
+        let kind = match result.kind {
+            TaskKind::Request => "request",
+            TaskKind::Group => "group",
+        };
+
+        let (name, tags) = self.metric(&format!("{}.duration", kind), result, &[]);
+        self.buffer.push(format!("{}:{}|ms{}", name, result.duration, tags));
+
+        let counter = self.metric(&format!("{}s", kind), result, &[]);
+        *self.counters.entry(counter).or_insert(0) += 1;
+
+        if result.error {
+            let error_type = result.error_type.as_str().to_string();
+            let counter = self.metric(&format!("{}.errors", kind), result, &[("error_type", error_type)]);
+            *self.counters.entry(counter).or_insert(0) += 1;
+        }
+    }
+
+    fn flush(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        for ((name, tags), counter) in std::mem::take(&mut self.counters) {
+            self.buffer.push(format!("{}:{}|c{}", name, counter, tags));
+        }
+        self.buffer.push(format!("{}.users:{}|g", self.prefix, results.current_users));
+        return self.buffer.flush();
+    }
+}
+
+impl Reporter for StatsdReporter {
+    fn on_message(&mut self, message: &ReportMessage) -> Result<(), String> {
+        for results in message.results.iter().flatten() {
+            for result in results.iter() {
+                self.record(result);
+            }
+        }
+        return Ok(());
+    }
+
+    fn on_interval(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        if self.buffer.is_due() {
+            return self.flush(results);
+        }
+        return Ok(());
+    }
+
+    fn on_finish(&mut self, results: &AggregatedResults) -> Result<(), String> {
+        return self.flush(results);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ErrorType;
+
+    fn reporter(dogstatsd: bool) -> StatsdReporter {
+        return StatsdReporter::new("127.0.0.1:8125".parse().unwrap(), "swarm", dogstatsd, &PushConfig::new()).unwrap();
+    }
+
+    #[test]
+    fn metric_segments_only_keep_safe_characters() {
+        assert_eq!(metric_segment("GET /users/{id}"), "GET__users__id_");
+        assert_eq!(metric_segment("a.b:c|d-e"), "a_b_c_d-e");
+    }
+
+    #[test]
+    fn tag_values_drop_datagram_separators() {
+        assert_eq!(tag_value("GET /users?a=1,b=2|#x\n"), "GET /users?a=1_b=2__x_");
+    }
+
+    #[test]
+    fn plain_statsd_encodes_labels_in_the_name() {
+        let result = TaskResult::request("main", "GET /users", 10).failed(Some(500), ErrorType::Request5xx);
+        let reporter = reporter(false);
+        assert_eq!(reporter.metric("request.duration", &result, &[]), ("swarm.main.GET__users.request.duration".to_string(), String::new()));
+        assert_eq!(reporter.metric("request.errors", &result, &[("error_type", "request_5xx".to_string()), ("group", String::new())]),
+            ("swarm.main.GET__users.request.errors.request_5xx".to_string(), String::new()));
+    }
+
+    #[test]
+    fn dogstatsd_uses_tags() {
+        let result = TaskResult::request("main", "GET /users", 10).failed(Some(500), ErrorType::Request5xx);
+        let reporter = reporter(true);
+        assert_eq!(reporter.metric("request.duration", &result, &[]),
+            ("swarm.request.duration".to_string(), "|#scenario:main,name:GET /users".to_string()));
+        assert_eq!(reporter.metric("request.errors", &result, &[("error_type", "request_5xx".to_string()), ("group", String::new())]),
+            ("swarm.request.errors".to_string(), "|#scenario:main,name:GET /users,error_type:request_5xx".to_string()));
+    }
+}
@@ -0,0 +1,343 @@
+use crate::config::{Limit, Metric, Threshold, ThresholdScope};
+use super::{AggregatedResults, Sample, error_rate, mean, percentile};
+
+#[derive(Clone, Debug)]
+pub struct ThresholdResult {
+    // e.g. "p95 of GET /users < 500ms"
+    pub name: String,
+    pub passed: bool,
+    // e.g. "p95 of GET /users = 812ms > 500ms", None when passed
+    pub failure: Option<String>,
+    // not judged yet during the run, too few requests so far
+    pub pending: bool,
+}
+
+pub fn matches_scope(scope: &ThresholdScope, scenario: &str, id: &str) -> bool {
+    return match scope {
+        ThresholdScope::Global => true,
+        ThresholdScope::Request(name) => id == name,
+        ThresholdScope::Scenario(name) => scenario == name,
+    };
+}
+
+fn collect(results: &AggregatedResults, scope: &ThresholdScope) -> Sample {
+    let mut sample = Sample::new();
+
+    for (scenario, scenario_results) in results.scenario_results.iter() {
+        if let ThresholdScope::Scenario(name) = scope {
+            if name != scenario {
+                continue;
+            }
+        }
+        for (id, url_results) in scenario_results.url_results.iter() {
+            if let ThresholdScope::Request(name) = scope {
+                if name != id {
+                    continue;
+                }
+            }
+            sample.requests += url_results.num_of_requests;
+            sample.errors += url_results.num_of_errors;
+            for (duration, counter) in url_results.durations.iter() {
+                *sample.durations.entry(*duration).or_insert(0) += counter;
+            }
+        }
+    }
+    return sample;
+}
+
+fn metric_value(metric: &Metric, sample: &Sample, duration: usize) -> f64 {
+    let durations = &sample.durations;
+    return match metric {
+        Metric::ErrorRate => error_rate(sample.errors, sample.requests),
+        Metric::MeanDuration => mean(durations) as f64,
+        Metric::P50 => percentile(durations, 50.0) as f64,
+        Metric::P90 => percentile(durations, 90.0) as f64,
+        Metric::P95 => percentile(durations, 95.0) as f64,
+        Metric::P99 => percentile(durations, 99.0) as f64,
+        Metric::MaxDuration => durations.keys().max().copied().unwrap_or(0) as f64,
+        Metric::Rps => {
+            if duration == 0 {
+                0.0
+            } else {
+                sample.requests as f64 / duration as f64
+            }
+        },
+        Metric::Requests => sample.requests as f64,
+    };
+}
+
+fn metric_name(metric: &Metric) -> &'static str {
+    return match metric {
+        Metric::ErrorRate => "error rate",
+        Metric::MeanDuration => "mean",
+        Metric::P50 => "p50",
+        Metric::P90 => "p90",
+        Metric::P95 => "p95",
+        Metric::P99 => "p99",
+        Metric::MaxDuration => "max",
+        Metric::Rps => "rps",
+        Metric::Requests => "requests",
+    };
+}
+
+fn metric_unit(metric: &Metric) -> &'static str {
+    return match metric {
+        Metric::ErrorRate => "%",
+        Metric::Rps => "/s",
+        Metric::Requests => "",
+        _ => "ms",
+    };
+}
+
+fn format_value(value: f64, unit: &str) -> String {
+    if value.fract() == 0.0 {
+        return format!("{}{}", value, unit);
+    }
+    return format!("{:.2}{}", value, unit);
+}
+
+// duration is the time in seconds the sample was collected over
+pub fn check_limit(metric: &Metric, scope: &ThresholdScope, limit: &Limit, sample: &Sample, duration: usize) -> ThresholdResult {
+    let scope = match scope {
+        ThresholdScope::Global => "all requests".to_string(),
+        ThresholdScope::Request(name) => name.clone(),
+        ThresholdScope::Scenario(name) => format!("scenario {}", name),
+    };
+    let unit = metric_unit(metric);
+    let value = metric_value(metric, sample, duration);
+    let metric = metric_name(metric);
+
+    let (passed, operator, violated) = match *limit {
+        Limit::Max(max) => (value <= max, "<=", (">", max)),
+        Limit::Min(min) => (value >= min, ">=", ("<", min)),
+    };
+
+    return ThresholdResult {
+        name: format!("{} of {} {} {}", metric, scope, operator, format_value(violated.1, unit)),
+        passed,
+        failure: if passed {
+            None
+        } else {
+            Some(format!("{} of {} = {} {} {}", metric, scope, format_value(value, unit), violated.0, format_value(violated.1, unit)))
+        },
+        pending: false,
+    };
+}
+
+// the count only grows, it is judged from the first request on
+fn has_enough_requests(threshold: &Threshold, sample: &Sample) -> bool {
+    return match threshold.metric {
+        Metric::Requests => true,
+        _ => sample.requests >= threshold.min_requests.max(1),
+    };
+}
+
+// Evaluates the thresholds for the hub, continuous ones on every interval, all of them at the end.
+pub struct ThresholdTracker {
+    thresholds: Vec<Threshold>,
+    // first failure of every continuous threshold, kept even when the metric recovers later
+    failures: Vec<Option<String>>,
+}
+
+impl ThresholdTracker {
+    pub fn new(thresholds: &[Threshold]) -> Result<ThresholdTracker, String> {
+        for threshold in thresholds.iter() {
+            if let (true, Metric::Requests, Limit::Min(_)) = (threshold.continuous, &threshold.metric, &threshold.limit) {
+                return Err("A minimum of requests can only be checked at the end, the threshold must not be continuous".to_string());
+            }
+        }
+        return Ok(ThresholdTracker {
+            thresholds: thresholds.to_vec(),
+            failures: vec![None; thresholds.len()],
+        });
+    }
+
+    // during the run only the continuous thresholds are reported
+    pub fn check(&mut self, results: &AggregatedResults, finished: bool) -> Vec<ThresholdResult> {
+        let mut threshold_results = vec![];
+        for (index, threshold) in self.thresholds.iter().enumerate() {
+            if !threshold.continuous && !finished {
+                continue;
+            }
+
+            let sample = collect(results, &threshold.scope);
+            let mut result = check_limit(&threshold.metric, &threshold.scope, &threshold.limit, &sample, results.duration);
+
+            let judged = finished || has_enough_requests(threshold, &sample);
+            if threshold.continuous && judged && self.failures[index].is_none() {
+                self.failures[index] = result.failure.clone()
+                    .map(|failure| format!("{} (after {}s)", failure, results.duration));
+            }
+            if let Some(failure) = &self.failures[index] {
+                result.passed = false;
+                result.failure = Some(failure.clone());
+            } else if !judged {
+                result.passed = true;
+                result.failure = None;
+                result.pending = true;
+            }
+            threshold_results.push(result);
+        }
+        return threshold_results;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{ErrorType, ReportMessage, TaskResult};
+
+    // 4 successful requests of 10, 20, 30 and 40ms and 1 failed request
+    fn sample() -> Sample {
+        let mut sample = Sample::new();
+        for duration in [10, 20, 30, 40] {
+            sample.add(&TaskResult::request("main", "GET /users", duration));
+        }
+        sample.add(&TaskResult::request("main", "GET /users", 0).failed(Some(500), ErrorType::Request5xx));
+        return sample;
+    }
+
+    // metric, its value for the sample over 2 seconds, how the threshold names it and shows the value
+    fn cases() -> Vec<(Metric, f64, &'static str, &'static str)> {
+        return vec![
+            (Metric::ErrorRate, 20.0, "error rate of all requests", "20%"),
+            (Metric::MeanDuration, 25.0, "mean of all requests", "25ms"),
+            (Metric::P50, 20.0, "p50 of all requests", "20ms"),
+            (Metric::P90, 40.0, "p90 of all requests", "40ms"),
+            (Metric::P95, 40.0, "p95 of all requests", "40ms"),
+            (Metric::P99, 40.0, "p99 of all requests", "40ms"),
+            (Metric::MaxDuration, 40.0, "max of all requests", "40ms"),
+            (Metric::Rps, 2.5, "rps of all requests", "2.50/s"),
+            (Metric::Requests, 5.0, "requests of all requests", "5"),
+        ];
+    }
+
+    fn unit(shown: &str) -> &str {
+        return shown.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    }
+
+    #[test]
+    fn max_limits_pass_up_to_the_limit() {
+        let sample = sample();
+        for (metric, value, name, shown) in cases() {
+            let result = check_limit(&metric, &ThresholdScope::Global, &Limit::Max(value), &sample, 2);
+            assert!(result.passed, "{:?}", metric);
+            assert_eq!(result.name, format!("{} <= {}", name, shown));
+            assert_eq!(result.failure, None);
+
+            let limit = value - 1.0;
+            let result = check_limit(&metric, &ThresholdScope::Global, &Limit::Max(limit), &sample, 2);
+            let limit = format_value(limit, unit(shown));
+            assert!(!result.passed, "{:?}", metric);
+            assert_eq!(result.name, format!("{} <= {}", name, limit));
+            assert_eq!(result.failure, Some(format!("{} = {} > {}", name, shown, limit)));
+        }
+    }
+
+    #[test]
+    fn min_limits_pass_from_the_limit() {
+        let sample = sample();
+        for (metric, value, name, shown) in cases() {
+            let result = check_limit(&metric, &ThresholdScope::Global, &Limit::Min(value), &sample, 2);
+            assert!(result.passed, "{:?}", metric);
+            assert_eq!(result.name, format!("{} >= {}", name, shown));
+            assert_eq!(result.failure, None);
+
+            let limit = value + 1.0;
+            let result = check_limit(&metric, &ThresholdScope::Global, &Limit::Min(limit), &sample, 2);
+            let limit = format_value(limit, unit(shown));
+            assert!(!result.passed, "{:?}", metric);
+            assert_eq!(result.name, format!("{} >= {}", name, limit));
+            assert_eq!(result.failure, Some(format!("{} = {} < {}", name, shown, limit)));
+        }
+    }
+
+    #[test]
+    fn names_show_the_scope() {
+        let sample = sample();
+        let result = check_limit(&Metric::P95, &ThresholdScope::Request("GET /users".to_string()), &Limit::Max(500.0), &sample, 2);
+        assert_eq!(result.name, "p95 of GET /users <= 500ms");
+        let result = check_limit(&Metric::ErrorRate, &ThresholdScope::Scenario("main".to_string()), &Limit::Max(1.5), &sample, 2);
+        assert_eq!(result.name, "error rate of scenario main <= 1.50%");
+        assert_eq!(result.failure, Some("error rate of scenario main = 20% > 1.50%".to_string()));
+    }
+
+    #[test]
+    fn rate_of_no_time_is_zero() {
+        let result = check_limit(&Metric::Rps, &ThresholdScope::Global, &Limit::Min(1.0), &sample(), 0);
+        assert_eq!(result.failure, Some("rps of all requests = 0/s < 1/s".to_string()));
+    }
+
+    fn add_requests(results: &mut AggregatedResults, count: usize, duration: usize, second: usize) {
+        let requests = (0..count).map(|_| TaskResult::request("main", "GET /users", duration)).collect();
+        results.add(ReportMessage { current_users: 1, results: vec![Ok(requests)], duration: second });
+    }
+
+    fn p95_below_100ms(continuous: bool) -> Threshold {
+        return Threshold::new(Metric::P95, ThresholdScope::Global, Limit::Max(100.0), continuous);
+    }
+
+    #[test]
+    fn continuous_thresholds_are_pending_until_enough_requests() {
+        let mut tracker = ThresholdTracker::new(&[p95_below_100ms(true)]).unwrap();
+        let mut results = AggregatedResults::new(1);
+
+        add_requests(&mut results, 19, 500, 1);
+        let result = &tracker.check(&results, false)[0];
+        assert!(result.passed && result.pending);
+        assert_eq!(result.failure, None);
+
+        add_requests(&mut results, 1, 500, 2);
+        let result = &tracker.check(&results, false)[0];
+        assert!(!result.passed && !result.pending);
+        assert_eq!(result.failure, Some("p95 of all requests = 500ms > 100ms (after 2s)".to_string()));
+    }
+
+    #[test]
+    fn continuous_failures_stick_after_recovery() {
+        let mut tracker = ThresholdTracker::new(&[p95_below_100ms(true)]).unwrap();
+        let mut results = AggregatedResults::new(1);
+
+        add_requests(&mut results, 20, 500, 1);
+        assert!(!tracker.check(&results, false)[0].passed);
+
+        add_requests(&mut results, 1000, 10, 2);
+        let result = &tracker.check(&results, false)[0];
+        assert!(!result.passed);
+        assert_eq!(result.failure, Some("p95 of all requests = 500ms > 100ms (after 1s)".to_string()));
+        let result = &tracker.check(&results, true)[0];
+        assert!(!result.passed);
+        assert_eq!(result.failure, Some("p95 of all requests = 500ms > 100ms (after 1s)".to_string()));
+    }
+
+    #[test]
+    fn the_end_judges_any_number_of_requests() {
+        let mut tracker = ThresholdTracker::new(&[p95_below_100ms(true), p95_below_100ms(false)]).unwrap();
+        let mut results = AggregatedResults::new(1);
+
+        add_requests(&mut results, 3, 500, 1);
+        let threshold_results = tracker.check(&results, false);
+        assert_eq!(threshold_results.len(), 1);
+        assert!(threshold_results[0].pending);
+
+        let threshold_results = tracker.check(&results, true);
+        assert_eq!(threshold_results.len(), 2);
+        assert!(threshold_results.iter().all(|result| !result.passed && !result.pending));
+    }
+
+    #[test]
+    fn request_counts_are_judged_from_the_start() {
+        let threshold = Threshold::new(Metric::Requests, ThresholdScope::Global, Limit::Max(2.0), true);
+        let mut tracker = ThresholdTracker::new(&[threshold]).unwrap();
+        let mut results = AggregatedResults::new(1);
+
+        add_requests(&mut results, 3, 10, 1);
+        let result = &tracker.check(&results, false)[0];
+        assert!(!result.passed && !result.pending);
+
+        let threshold = Threshold::new(Metric::Requests, ThresholdScope::Global, Limit::Min(2.0), true);
+        assert!(ThresholdTracker::new(&[threshold]).is_err());
+        let threshold = Threshold::new(Metric::Requests, ThresholdScope::Global, Limit::Min(2.0), false);
+        assert!(ThresholdTracker::new(&[threshold]).is_ok());
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::time::SystemTime;
+
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ErrorType {
@@ -7,17 +10,111 @@ pub enum ErrorType {
     RequestOther,
     Timeout,
     Connection,
+    // response body could not be decoded according to its content-encoding
+    Decoding,
     Internal,
 }
 
+impl ErrorType {
+    // stable name used in exported reports
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            ErrorType::Request5xx => "request_5xx",
+            ErrorType::Request4xx => "request_4xx",
+            ErrorType::RequestOther => "request_other",
+            ErrorType::Timeout => "timeout",
+            ErrorType::Connection => "connection",
+            ErrorType::Decoding => "decoding",
+            ErrorType::Internal => "internal",
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskKind {
+    Request,
+    Group,
+}
+
 #[derive(Clone, Debug)]
 pub struct TaskResult {
+    pub kind: TaskKind,
+    // wall clock time the task started at
+    pub started_at: SystemTime,
+    pub user_id: usize,
     pub id: String,
+    // innermost group the task ran in
+    pub group: Option<String>,
+    pub scenario: String,
     pub url: String,
     pub success: bool,
     pub error: bool,
     pub error_type: ErrorType,
     pub duration: usize,
+    // milliseconds until the response headers arrived, None when no response was received
+    pub time_to_first_byte: Option<usize>,
+    // milliseconds spent opening the connection, only set for requests that opened a new one
+    pub connect_time: Option<usize>,
+    pub tls_time: Option<usize>,
+    // negotiated protocol, e.g. "HTTP/2.0", None when no response was received
+    pub http_version: Option<String>,
+    // None when no response was received
+    pub reused_connection: Option<bool>,
+    // ip the request was sent to (the proxy when one is used), None when no response was received
+    pub remote_addr: Option<String>,
+    // local source address the user is bound to, None when not configured
+    pub local_addr: Option<String>,
+    pub status: Option<u16>,
+    // 1 for the original request, higher for retries
+    pub attempt: usize,
+    // the attempt failed and another one followed it
+    pub retried: bool,
+    // response body size as sent by the server and after content decoding
+    pub bytes_received: usize,
+    pub bytes_decoded: usize,
+    // milliseconds spent waiting for the rate limiters, not included in duration
+    pub throttled: usize,
+}
+
+#[cfg(test)]
+impl TaskResult {
+    // a successful request, tests change the fields they care about
+    pub fn request(scenario: &str, id: &str, duration: usize) -> TaskResult {
+        return TaskResult {
+            kind: TaskKind::Request,
+            started_at: SystemTime::now(),
+            user_id: 0,
+            id: id.to_string(),
+            group: None,
+            scenario: scenario.to_string(),
+            url: String::new(),
+            success: true,
+            error: false,
+            error_type: ErrorType::RequestOther,
+            duration,
+            time_to_first_byte: Some(duration),
+            connect_time: None,
+            tls_time: None,
+            http_version: Some("HTTP/1.1".to_string()),
+            reused_connection: Some(true),
+            remote_addr: None,
+            local_addr: None,
+            status: Some(200),
+            attempt: 1,
+            retried: false,
+            bytes_received: 0,
+            bytes_decoded: 0,
+            throttled: 0,
+        };
+    }
+
+    pub fn failed(mut self, status: Option<u16>, error_type: ErrorType) -> TaskResult {
+        self.success = false;
+        self.error = true;
+        self.status = status;
+        self.error_type = error_type;
+        return self;
+    }
 }
 
 pub type UserResult = Result<Vec<TaskResult>, String>;
@@ -31,6 +128,16 @@ pub struct ReportMessage {
 
 #[derive(Clone, Debug)]
 pub enum UserStatus {
-    Created,
-    Finished(UserResult),
+    // index of the user pool the user belongs to
+    Created(usize),
+    // sent as soon as a task finishes, so long scenarios are reported while they run
+    Result(Box<TaskResult>),
+    Finished(usize, Result<(), String>),
+}
+
+// How a user talks to the runner: results go out as they come, the stop reason comes in.
+#[derive(Clone)]
+pub struct UserChannels {
+    pub status_sender: Sender<UserStatus>,
+    pub stop_receiver: watch::Receiver<Option<String>>,
 }
\ No newline at end of file
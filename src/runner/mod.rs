@@ -2,9 +2,13 @@ mod runner;
 // mod asynchronous;
 mod message;
 mod scheduler;
+mod scenario;
+mod pool;
 
 // pub use asynchronous::AsyncRunner;
 pub use runner::*;
 pub use message::*;
 pub use scheduler::*;
+pub use scenario::*;
+pub use pool::*;
 // pub use message::TaskResult;
\ No newline at end of file
@@ -0,0 +1,79 @@
+use crate::config::{Config, Scenario, Workload};
+use super::{Scheduler, ScenarioPicker};
+
+// Users driven by one workload, the main workload runs all scenarios without their own workload.
+pub struct UserPool {
+    pub scenarios: Vec<Scenario>,
+    pub picker: ScenarioPicker,
+    pub users_counter: usize,
+    workload: Workload,
+    start_offset: usize,
+    scheduler: Option<Scheduler>,
+    // no new users are spawned, the pool is done when the running ones finish
+    stopped: bool,
+    done: bool,
+}
+
+impl UserPool {
+    fn new(workload: Workload, start_offset: usize, scenarios: Vec<Scenario>) -> UserPool {
+        return UserPool {
+            picker: ScenarioPicker::new(&scenarios),
+            scenarios,
+            users_counter: 0,
+            workload,
+            start_offset,
+            scheduler: None,
+            stopped: false,
+            done: false,
+        };
+    }
+
+    pub fn from_config(config: &Config) -> Vec<UserPool> {
+        let mut pools = vec![];
+
+        let main_scenarios: Vec<Scenario> = config.scenarios.iter()
+            .filter(|scenario| scenario.workload.is_none())
+            .cloned()
+            .collect();
+        if !main_scenarios.is_empty() {
+            pools.push(UserPool::new(config.workload.clone(), 0, main_scenarios));
+        }
+
+        for scenario in config.scenarios.iter() {
+            if let Some(workload) = &scenario.workload {
+                // the weight only splits the main workload, a weight of 0 would leave this pool without users
+                let mut scenario = scenario.clone();
+                scenario.weight = 1;
+                pools.push(UserPool::new(workload.clone(), scenario.start_offset, vec![scenario]));
+            }
+        }
+
+        return pools;
+    }
+
+    pub fn is_done(&self) -> bool {
+        return self.done;
+    }
+
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    // number of users the pool should have now, None when the pool is not running
+    pub fn target_users(&mut self, elapsed: usize) -> Option<usize> {
+        if self.stopped {
+            self.done = self.users_counter == 0;
+            return None;
+        }
+        if self.done || elapsed < self.start_offset {
+            return None;
+        }
+
+        let scheduler = self.scheduler.get_or_insert_with(|| Scheduler::new(self.workload.clone()));
+        let target = scheduler.next();
+        if target.is_none() && self.users_counter == 0 {
+            self.done = true;
+        }
+        return target;
+    }
+}
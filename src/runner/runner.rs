@@ -1,13 +1,16 @@
+use std::sync::Arc;
+
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time::{Duration, Instant, interval};
 use tokio::task::JoinHandle;
 
 use crate::config::{Config};
-use crate::runner::{UserResult, Scheduler};
-use crate::user::http_user;
-use super::{ReportMessage, UserStatus};
+use crate::runner::{UserResult, UserPool};
+use crate::user::{http_user, HttpClients, RateLimiters};
+use super::{ReportMessage, TaskResult, UserChannels, UserStatus};
 
-fn spawn_users(config: &Config, mut users_to_add: usize, status_sender: &Sender<UserStatus>) -> () {
+fn spawn_users(pool_index: usize, pool: &mut UserPool, clients: &HttpClients, limiters: &RateLimiters, next_user_id: &mut usize, mut users_to_add: usize, channels: &UserChannels) {
 
     if users_to_add == 0 {
         return;
@@ -19,69 +22,117 @@ fn spawn_users(config: &Config, mut users_to_add: usize, status_sender: &Sender<
 
     let mut i = 0;
     while i < users_to_add {
-        let schedule = config.schedule.clone();
-        let status_sender = status_sender.clone();
+        let scenario = match pool.picker.next() {
+            Some(index) => pool.scenarios[index].clone(),
+            None => return,
+        };
+        let user_client = clients.for_user();
+        let user_limiters = limiters.for_user();
+        let user_id = *next_user_id;
+        *next_user_id += 1;
+        let channels = channels.clone();
         tokio::spawn(async move {
-            status_sender.send(UserStatus::Created).await.unwrap();
-            let user_result = http_user(schedule).await;
-            status_sender.send(UserStatus::Finished(user_result)).await.unwrap();
+            let status_sender = channels.status_sender.clone();
+            status_sender.send(UserStatus::Created(pool_index)).await.unwrap();
+            let user_result = http_user(user_id, scenario, user_client, user_limiters, channels).await;
+            status_sender.send(UserStatus::Finished(pool_index, user_result)).await.unwrap();
         });
         i += 1;
     }
 }
 
-async fn runner(config: Config, report_sender: Sender<ReportMessage>) -> () {
+// Stops the run gracefully: no new users are started and the running ones stop before their next task.
+#[derive(Clone)]
+pub struct RunnerStopper {
+    sender: Arc<watch::Sender<Option<String>>>,
+}
+
+impl RunnerStopper {
+    pub fn stop(&self, reason: &str) {
+        // the first reason wins
+        if self.sender.borrow().is_none() {
+            let _ = self.sender.send(Some(reason.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+impl RunnerStopper {
+    // a stopper without a runner, tests watch the receiver instead
+    pub fn new() -> (RunnerStopper, watch::Receiver<Option<String>>) {
+        let (sender, receiver) = watch::channel::<Option<String>>(None);
+        return (RunnerStopper { sender: Arc::new(sender) }, receiver);
+    }
+}
+
+async fn runner(config: Config, clients: Arc<HttpClients>, limiters: Arc<RateLimiters>, report_sender: Sender<ReportMessage>, stop_receiver: watch::Receiver<Option<String>>) -> () {
     let (status_sender, mut status_receiver) = channel::<UserStatus>(1000);
+    let channels = UserChannels {
+        status_sender,
+        stop_receiver: stop_receiver.clone(),
+    };
     // let (done_sender, mut done_receiver) = channel::<bool>(1);
 
     let started_at = Instant::now();
     let mut interval = interval(Duration::from_millis(200));
 
-    let mut users_counter: usize = 0;
     let mut queued_results: Vec<UserResult> = vec![];
+    // results streamed by the users since the last report
+    let mut queued_tasks: Vec<TaskResult> = vec![];
 
-    let mut scheduler = Scheduler::new(config.workload.clone());
+    let mut pools = UserPool::from_config(&config);
+    let mut next_user_id: usize = 0;
 
     loop {
         tokio::select! {
             _ = interval.tick() => {
 
                 // send aggregated results
+                if !queued_tasks.is_empty() {
+                    queued_results.push(Ok(std::mem::take(&mut queued_tasks)));
+                }
                 report_sender.send(ReportMessage {
-                    current_users: users_counter,
+                    current_users: pools.iter().map(|pool| pool.users_counter).sum(),
                     results: queued_results.clone(),
                     duration: started_at.elapsed().as_secs().try_into().unwrap(),
                 }).await.unwrap();
 
                 queued_results.clear();
 
-                let target_num_users = scheduler.next();
+                if stop_receiver.borrow().is_some() {
+                    pools.iter_mut().for_each(|pool| pool.stop());
+                }
 
-                if let Some(target_num_users) = target_num_users {
-                    spawn_users(&config, target_num_users - users_counter, &status_sender);
-                } else {
-                    if users_counter == 0 {
-                        //wait till all users finish
-                        break;
+                let elapsed: usize = started_at.elapsed().as_secs().try_into().unwrap();
+                for (pool_index, pool) in pools.iter_mut().enumerate() {
+                    if let Some(target_num_users) = pool.target_users(elapsed) {
+                        let users_to_add = target_num_users.saturating_sub(pool.users_counter);
+                        spawn_users(pool_index, pool, &clients, &limiters, &mut next_user_id, users_to_add, &channels);
                     }
-                    
+                }
+
+                //wait till all users finish
+                if pools.iter().all(|pool| pool.is_done()) {
+                    break;
                 }
             },
             msg = status_receiver.recv() => {
-                match msg {
-                    Some(user_status) => {
-                        // receive user status, update counter, aggregate results
-                        match user_status {
-                            UserStatus::Created => {
-                                users_counter += 1;
-                            },
-                            UserStatus::Finished(result) => {
-                                users_counter -= 1;
-                                queued_results.push(result);
-                            },
-                        }
-                    },
-                    None => {}
+                if let Some(user_status) = msg {
+                    // receive user status, update counter, aggregate results
+                    match user_status {
+                        UserStatus::Created(pool_index) => {
+                            pools[pool_index].users_counter += 1;
+                        },
+                        UserStatus::Result(result) => {
+                            queued_tasks.push(*result);
+                        },
+                        UserStatus::Finished(pool_index, result) => {
+                            pools[pool_index].users_counter -= 1;
+                            if let Err(err) = result {
+                                queued_results.push(Err(err));
+                            }
+                        },
+                    }
                 }
             },
         }
@@ -91,26 +142,33 @@ async fn runner(config: Config, report_sender: Sender<ReportMessage>) -> () {
 pub struct Runner {
     receiver: Option<Receiver<ReportMessage>>,
     runner_handle: Option<JoinHandle<()>>,
+    stopper: RunnerStopper,
 }
 
 impl Runner {
-    pub fn start(config: Config) -> Runner {
+    pub fn start(config: Config) -> Result<Runner, String> {
         let (report_sender, report_receiver) = channel::<ReportMessage>(100);
+        let clients = Arc::new(HttpClients::new(&config.http)?);
+        let (stop_sender, stop_receiver) = watch::channel::<Option<String>>(None);
+        let limiters = Arc::new(RateLimiters::new(&config.rate_limit, stop_receiver.clone()));
 
         let runner_handle = {
             // let sender = status_sender.clone();
             let config = config.clone();
             // let users_counter = users_counter.clone();
             let handler = tokio::spawn(async move {
-                runner(config, report_sender).await;
+                runner(config, clients, limiters, report_sender, stop_receiver).await;
             });
             handler
         };
 
-        return Runner {
+        return Ok(Runner {
             receiver: Some(report_receiver),
             runner_handle: Some(runner_handle),
-        };
+            stopper: RunnerStopper {
+                sender: Arc::new(stop_sender),
+            },
+        });
     }
 
     pub fn take_receiver(&mut self) -> Result<Receiver<ReportMessage>, &str> {
@@ -120,6 +178,10 @@ impl Runner {
         return Err("Runner is either not started or receiver already taken");
     }
 
+    pub fn stopper(&self) -> RunnerStopper {
+        return self.stopper.clone();
+    }
+
     pub async fn wait_until_finished(&mut self) {
         if let Some(runner) = self.runner_handle.take() {
             runner.await.unwrap();
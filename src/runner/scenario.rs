@@ -0,0 +1,82 @@
+use crate::config::Scenario;
+
+// Smooth weighted round-robin, so every window of users follows the weights as closely as possible
+// (weights 5, 1, 1 give a, a, b, a, c, a, a instead of a, a, a, a, a, b, c).
+pub struct ScenarioPicker {
+    weights: Vec<isize>,
+    current: Vec<isize>,
+    total: isize,
+}
+
+impl ScenarioPicker {
+    pub fn new(scenarios: &[Scenario]) -> ScenarioPicker {
+        let weights: Vec<isize> = scenarios.iter()
+            .map(|scenario| scenario.weight.try_into().unwrap())
+            .collect();
+        let total = weights.iter().sum();
+
+        return ScenarioPicker {
+            current: vec![0; weights.len()],
+            weights,
+            total,
+        };
+    }
+}
+
+impl Iterator for ScenarioPicker {
+
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.total <= 0 {
+            return None;
+        }
+
+        let mut selected = 0;
+        for index in 0..self.weights.len() {
+            self.current[index] += self.weights[index];
+            if self.current[index] > self.current[selected] {
+                selected = index;
+            }
+        }
+        self.current[selected] -= self.total;
+
+        return Some(selected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Schedule;
+
+    fn scenarios(weights: &[usize]) -> Vec<Scenario> {
+        return weights.iter()
+            .map(|weight| Scenario {
+                name: String::new(),
+                weight: *weight,
+                schedule: Schedule { tasks: vec![], think_time: None, pacing: None },
+                workload: None,
+                start_offset: 0,
+            })
+            .collect();
+    }
+
+    #[test]
+    fn spreads_scenarios_by_weight() {
+        let picked: Vec<usize> = ScenarioPicker::new(&scenarios(&[5, 1, 1])).take(14).collect();
+        assert_eq!(picked, vec![0, 0, 1, 0, 2, 0, 0, 0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn equal_weights_alternate() {
+        let picked: Vec<usize> = ScenarioPicker::new(&scenarios(&[1, 1])).take(4).collect();
+        assert_eq!(picked, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn zero_weights_pick_nothing() {
+        assert_eq!(ScenarioPicker::new(&scenarios(&[0, 0])).next(), None);
+        assert_eq!(ScenarioPicker::new(&scenarios(&[])).next(), None);
+    }
+}
@@ -53,8 +53,59 @@ impl Iterator for Scheduler {
                     return Some(res);
                 }
             },
-            Workload::EaseOut { duration, max_users, ramp_up_time } => todo!(),
-            Workload::Sin { duration, max_users, min_users, cycle_time } => todo!(),
+            Workload::EaseOut { duration, max_users, ramp_up_time } => {
+                if elapsed > duration {
+                    self.done = true;
+                    return None;
+                }
+                return Some(ease_out(elapsed, max_users, ramp_up_time));
+            },
+            Workload::Sin { duration, max_users, min_users, cycle_time } => {
+                if elapsed > duration {
+                    self.done = true;
+                    return None;
+                }
+                return Some(sine(elapsed, min_users, max_users, cycle_time));
+            },
         };
     }
+}
+
+// quadratic ease-out, adds users quickly at first and slows down towards max_users
+fn ease_out(elapsed: usize, max_users: usize, ramp_up_time: usize) -> usize {
+    if elapsed >= ramp_up_time {
+        return max_users;
+    }
+    let remaining = 1.0 - elapsed as f64 / ramp_up_time as f64;
+    let users = (max_users as f64 * (1.0 - remaining * remaining)).round() as usize;
+    return users.max(1);
+}
+
+// starts at min_users, reaches max_users half way through every cycle
+fn sine(elapsed: usize, min_users: usize, max_users: usize, cycle_time: usize) -> usize {
+    if cycle_time == 0 || max_users <= min_users {
+        return max_users;
+    }
+    let phase = (elapsed % cycle_time) as f64 / cycle_time as f64 * std::f64::consts::TAU;
+    let amplitude = (max_users - min_users) as f64;
+    return min_users + (amplitude * (1.0 - phase.cos()) / 2.0).round() as usize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ease_out_ramps_up_fast_then_slowly() {
+        let users: Vec<usize> = (0..=5).map(|elapsed| ease_out(elapsed, 100, 4)).collect();
+        assert_eq!(users, vec![1, 44, 75, 94, 100, 100]);
+        assert_eq!(ease_out(0, 10, 0), 10);
+    }
+
+    #[test]
+    fn sine_cycles_between_min_and_max() {
+        let users: Vec<usize> = (0..=8).map(|elapsed| sine(elapsed, 10, 30, 4)).collect();
+        assert_eq!(users, vec![10, 20, 30, 20, 10, 20, 30, 20, 10]);
+        assert_eq!(sine(3, 10, 30, 0), 30);
+    }
 }
\ No newline at end of file
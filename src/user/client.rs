@@ -0,0 +1,154 @@
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use hyper::{Body, Client, Request};
+use tokio::time::Duration;
+use crate::config::{HttpConfig, HttpVersion, ConnectionPool, CompressionConfig};
+use super::connector::Connector;
+use super::proxy::Proxy;
+
+pub type HttpClient = Client<Connector>;
+
+pub struct UserClient {
+    pub client: HttpClient,
+    pub local_address: Option<IpAddr>,
+    pub compression: CompressionConfig,
+    proxy: Option<Proxy>,
+}
+
+impl UserClient {
+    // requests forwarded to an http proxy carry its credentials, tunneled ones send them with CONNECT
+    pub fn authorize_proxy(&self, request: &mut Request<Body>) {
+        if let Some(proxy) = self.proxy.as_ref().filter(|proxy| proxy.forwards(request.uri())) {
+            if let Some(authorization) = proxy.authorization() {
+                request.headers_mut().insert("proxy-authorization", authorization.parse().unwrap());
+            }
+        }
+    }
+}
+
+struct Source {
+    local_address: Option<IpAddr>,
+    connector: Connector,
+    current: Mutex<Option<(HttpClient, usize)>>,
+}
+
+// Hands out http clients to users according to the connection pool policy.
+// Users sharing a client share its connections, which is how HTTP/2 streams get multiplexed.
+// Every local source address gets its own connections, users are assigned to sources round-robin.
+pub struct HttpClients {
+    config: HttpConfig,
+    sources: Vec<Source>,
+    next_source: AtomicUsize,
+    proxy: Option<Proxy>,
+}
+
+impl HttpClients {
+    pub fn new(config: &HttpConfig) -> Result<HttpClients, String> {
+        if config.http_version != HttpVersion::Http1
+            && config.connection.pool == ConnectionPool::PerRequest
+            && config.http2.streams_per_connection.is_some() {
+            return Err("HTTP/2 streams_per_connection needs kept connections, it can't be used with the per request pool".to_string());
+        }
+
+        let local_addresses = if config.local_addresses.is_empty() {
+            vec![None]
+        } else {
+            config.local_addresses.iter().map(|address| Some(*address)).collect()
+        };
+
+        let mut sources = vec![];
+        for local_address in local_addresses {
+            sources.push(Source {
+                local_address,
+                connector: Connector::new(config, local_address)?,
+                current: Mutex::new(None),
+            });
+        }
+
+        let proxy = match &config.proxy {
+            Some(proxy) => Some(Proxy::new(proxy)?),
+            None => None,
+        };
+
+        return Ok(HttpClients {
+            config: config.clone(),
+            sources,
+            next_source: AtomicUsize::new(0),
+            proxy,
+        });
+    }
+
+    fn build_client(&self, connector: &Connector) -> HttpClient {
+        let mut builder = Client::builder();
+
+        let connection = &self.config.connection;
+        if connection.pool == ConnectionPool::PerRequest {
+            builder.pool_max_idle_per_host(0);
+        } else if let Some(max_idle) = connection.max_idle {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = connection.idle_timeout {
+            builder.pool_idle_timeout(Duration::from_secs(idle_timeout.try_into().unwrap()));
+        }
+
+        if self.config.http_version != HttpVersion::Http1 {
+            let http2 = &self.config.http2;
+            builder
+                .http2_only(self.config.http_version == HttpVersion::Http2PriorKnowledge)
+                .http2_adaptive_window(http2.adaptive_window)
+                .http2_initial_stream_window_size(http2.initial_stream_window_size)
+                .http2_initial_connection_window_size(http2.initial_connection_window_size);
+        }
+
+        return builder.build(connector.clone());
+    }
+
+    fn users_per_client(&self) -> usize {
+        let pool = &self.config.connection.pool;
+        if self.config.http_version == HttpVersion::Http1 {
+            return if *pool == ConnectionPool::Shared { usize::MAX } else { 1 };
+        }
+        // an explicit number of streams multiplexes users whatever the pool, as long as connections are kept
+        return match (pool, self.config.http2.streams_per_connection) {
+            (ConnectionPool::PerRequest, _) => 1,
+            (_, Some(streams)) => streams.max(1),
+            (ConnectionPool::Shared, None) => usize::MAX,
+            (ConnectionPool::PerUser, None) => 1,
+        };
+    }
+
+    fn client_for_source(&self, source: &Source) -> HttpClient {
+        let users_per_client = self.users_per_client();
+        if users_per_client == 1 {
+            return self.build_client(&source.connector);
+        }
+
+        // every user sends one request at a time, so an HTTP/2 client shared by N users
+        // never has more than N streams open on its connection
+        let mut current = source.current.lock().unwrap();
+        if let Some((client, users)) = current.as_mut() {
+            if *users < users_per_client {
+                *users += 1;
+                return client.clone();
+            }
+        }
+
+        let client = self.build_client(&source.connector);
+        *current = Some((client.clone(), 1));
+        return client;
+    }
+
+    pub fn for_user(&self) -> UserClient {
+        let index = self.next_source.fetch_add(1, Ordering::Relaxed) % self.sources.len();
+        let source = &self.sources[index];
+
+        return UserClient {
+            client: self.client_for_source(source),
+            local_address: source.local_address,
+            compression: self.config.compression.clone(),
+            proxy: self.proxy.clone(),
+        };
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::{self, Read, Write};
+
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use crate::config::Encoding;
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+pub fn encoding_name(encoding: &Encoding) -> &'static str {
+    return match encoding {
+        Encoding::Gzip => "gzip",
+        Encoding::Deflate => "deflate",
+        Encoding::Brotli => "br",
+        Encoding::Zstd => "zstd",
+    };
+}
+
+pub fn accept_encoding(encodings: &[Encoding]) -> String {
+    return encodings.iter()
+        .map(encoding_name)
+        .collect::<Vec<&str>>()
+        .join(", ");
+}
+
+pub fn compress(encoding: &Encoding, data: &[u8]) -> io::Result<Vec<u8>> {
+    return match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        },
+        // "deflate" in HTTP is the zlib format
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        },
+        Encoding::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW);
+            encoder.write_all(data)?;
+            Ok(encoder.into_inner())
+        },
+        Encoding::Zstd => zstd::stream::encode_all(data, 0),
+    };
+}
+
+fn decode_one(name: &str, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut decoded = vec![];
+    match name {
+        "identity" => return Ok(data),
+        "gzip" | "x-gzip" => {
+            GzDecoder::new(&data[..]).read_to_end(&mut decoded)?;
+        },
+        "deflate" => {
+            ZlibDecoder::new(&data[..]).read_to_end(&mut decoded)?;
+        },
+        "br" => {
+            brotli::Decompressor::new(&data[..], BROTLI_BUFFER_SIZE).read_to_end(&mut decoded)?;
+        },
+        "zstd" => {
+            decoded = zstd::stream::decode_all(&data[..])?;
+        },
+        _ => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported content-encoding {}", name)));
+        },
+    }
+    return Ok(decoded);
+}
+
+// content-encoding lists encodings in the order they were applied
+pub fn decode(content_encoding: &str, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut decoded = data;
+    for name in content_encoding.rsplit(',') {
+        let name = name.trim().to_lowercase();
+        if !name.is_empty() {
+            decoded = decode_one(&name, decoded)?;
+        }
+    }
+    return Ok(decoded);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"{\"users\": [\"alice\", \"bob\", \"carol\", \"alice\", \"bob\", \"carol\"]}";
+
+    #[test]
+    fn decodes_what_it_compresses() {
+        for encoding in [Encoding::Gzip, Encoding::Deflate, Encoding::Brotli, Encoding::Zstd] {
+            let compressed = compress(&encoding, BODY).unwrap();
+            assert_ne!(compressed, BODY);
+            assert_eq!(decode(encoding_name(&encoding), compressed).unwrap(), BODY);
+        }
+    }
+
+    #[test]
+    fn decodes_bodies_compressed_elsewhere() {
+        // "hello" from python's gzip.compress(mtime=0) and zlib.compress
+        let gzip = vec![31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 7, 0, 134, 166, 16, 54, 5, 0, 0, 0];
+        let deflate = vec![120, 156, 203, 72, 205, 201, 201, 7, 0, 6, 44, 2, 21];
+        assert_eq!(decode("gzip", gzip.clone()).unwrap(), b"hello");
+        assert_eq!(decode("x-gzip", gzip).unwrap(), b"hello");
+        assert_eq!(decode("deflate", deflate).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn decodes_stacked_encodings_in_reverse_order() {
+        let gzipped = compress(&Encoding::Gzip, BODY).unwrap();
+        let twice = compress(&Encoding::Brotli, &gzipped).unwrap();
+        assert_eq!(decode("gzip, BR", twice).unwrap(), BODY);
+        assert_eq!(decode("identity", BODY.to_vec()).unwrap(), BODY);
+        assert_eq!(decode("", BODY.to_vec()).unwrap(), BODY);
+    }
+
+    #[test]
+    fn rejects_unknown_encodings_and_corrupted_bodies() {
+        let error = decode("compress", BODY.to_vec()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Unsupported content-encoding compress");
+        assert!(decode("gzip", BODY.to_vec()).is_err());
+    }
+
+    #[test]
+    fn accept_encoding_lists_the_names() {
+        assert_eq!(accept_encoding(&[Encoding::Brotli, Encoding::Gzip, Encoding::Deflate]), "br, gzip, deflate");
+    }
+}
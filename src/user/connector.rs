@@ -0,0 +1,256 @@
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use hyper::client::HttpConnector;
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use hyper::Uri;
+use native_tls::{Certificate, Identity, Protocol};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, Instant};
+use tokio_native_tls::{TlsConnector, TlsStream};
+use crate::config::{HttpConfig, HttpVersion, TlsConfig, TlsVersion, ClientIdentity};
+use super::proxy::Proxy;
+use super::resolver::Resolver;
+
+pub type ConnectorError = Box<dyn std::error::Error + Send + Sync>;
+
+fn read_file(path: &str, what: &str) -> Result<Vec<u8>, String> {
+    return fs::read(path).map_err(|err| format!("Cannot read {} {}: {}", what, path, err));
+}
+
+fn to_native_protocol(version: &TlsVersion) -> Protocol {
+    return match version {
+        TlsVersion::Tls10 => Protocol::Tlsv10,
+        TlsVersion::Tls11 => Protocol::Tlsv11,
+        TlsVersion::Tls12 => Protocol::Tlsv12,
+        TlsVersion::Tls13 => Protocol::Tlsv13,
+    };
+}
+
+fn build_identity(identity: &ClientIdentity) -> Result<Identity, String> {
+    return match identity {
+        ClientIdentity::Pem { certificate, key } => {
+            let certificate = read_file(certificate, "client certificate")?;
+            let key = read_file(key, "client key")?;
+            Identity::from_pkcs8(&certificate, &key)
+                .map_err(|err| format!("Invalid PEM client identity: {}", err))
+        },
+        ClientIdentity::Pkcs12 { path, password } => {
+            let archive = read_file(path, "PKCS #12 archive")?;
+            Identity::from_pkcs12(&archive, password)
+                .map_err(|err| format!("Invalid PKCS #12 client identity: {}", err))
+        },
+    };
+}
+
+fn build_tls_connector(config: &TlsConfig, http_version: &HttpVersion) -> Result<TlsConnector, String> {
+    let mut builder = native_tls::TlsConnector::builder();
+
+    match http_version {
+        HttpVersion::Http1 => {},
+        HttpVersion::Http2 => {
+            builder.request_alpns(&["h2", "http/1.1"]);
+        },
+        HttpVersion::Http2PriorKnowledge => {
+            builder.request_alpns(&["h2"]);
+        },
+    }
+
+    for path in config.root_certificates.iter() {
+        let pem = read_file(path, "CA certificate")?;
+        let certificate = Certificate::from_pem(&pem)
+            .map_err(|err| format!("Invalid CA certificate {}: {}", path, err))?;
+        builder.add_root_certificate(certificate);
+    }
+
+    if let Some(identity) = &config.identity {
+        builder.identity(build_identity(identity)?);
+    }
+
+    if config.insecure_skip_verify {
+        builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_hostnames(true);
+    }
+
+    if let Some(version) = &config.min_version {
+        builder.min_protocol_version(Some(to_native_protocol(version)));
+    }
+
+    let connector = builder.build().map_err(|err| format!("Cannot create TLS connector: {}", err))?;
+    return Ok(TlsConnector::from(connector));
+}
+
+// Attached to every response, tells whether the request was sent over a new or a reused connection
+// and how long it took to establish it.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    requests: Arc<AtomicUsize>,
+    // dns lookup, tcp connect and proxy tunnel
+    pub connect_time: Duration,
+    pub tls_time: Option<Duration>,
+}
+
+impl ConnectionInfo {
+    fn new(connect_time: Duration, tls_time: Option<Duration>) -> ConnectionInfo {
+        return ConnectionInfo {
+            requests: Arc::new(AtomicUsize::new(0)),
+            connect_time,
+            tls_time,
+        };
+    }
+
+    // marks another request sent over the connection, returns true if it wasn't the first one
+    pub fn track_request(&self) -> bool {
+        return self.requests.fetch_add(1, Ordering::Relaxed) > 0;
+    }
+}
+
+pub enum MaybeTlsStream {
+    Plain(TcpStream),
+    Tls(TlsStream<TcpStream>),
+}
+
+pub struct ConnectorStream {
+    stream: MaybeTlsStream,
+    info: ConnectionInfo,
+    // connected to an http proxy that gets the requests in absolute-form
+    forwarding: bool,
+}
+
+impl Connection for ConnectorStream {
+    fn connected(&self) -> Connected {
+        let connected = match &self.stream {
+            MaybeTlsStream::Plain(stream) => stream.connected(),
+            MaybeTlsStream::Tls(stream) => {
+                let connected = stream.get_ref().get_ref().get_ref().connected();
+                match stream.get_ref().negotiated_alpn() {
+                    Ok(Some(protocol)) if protocol == b"h2" => connected.negotiated_h2(),
+                    _ => connected,
+                }
+            },
+        };
+        return connected.proxy(self.forwarding).extra(self.info.clone());
+    }
+}
+
+impl AsyncRead for ConnectorStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        return match &mut self.get_mut().stream {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        };
+    }
+}
+
+impl AsyncWrite for ConnectorStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        return match &mut self.get_mut().stream {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        };
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        return match &mut self.get_mut().stream {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        };
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        return match &mut self.get_mut().stream {
+            MaybeTlsStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            MaybeTlsStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct Connector {
+    http: HttpConnector<Resolver>,
+    resolver: Resolver,
+    tls: TlsConnector,
+    sni: Option<String>,
+    proxy: Option<Proxy>,
+}
+
+impl Connector {
+    pub fn new(config: &HttpConfig, local_address: Option<IpAddr>) -> Result<Connector, String> {
+        let resolver = Resolver::new(&config.dns);
+        let mut http = HttpConnector::new_with_resolver(resolver.clone());
+        http.enforce_http(false);
+        http.set_local_address(local_address);
+
+        let proxy = match &config.proxy {
+            Some(proxy) => Some(Proxy::new(proxy)?),
+            None => None,
+        };
+
+        return Ok(Connector {
+            http,
+            resolver,
+            tls: build_tls_connector(&config.tls, &config.http_version)?,
+            sni: config.tls.sni.clone(),
+            proxy,
+        });
+    }
+}
+
+impl Service<Uri> for Connector {
+    type Response = ConnectorStream;
+    type Error = ConnectorError;
+    type Future = Pin<Box<dyn Future<Output = Result<ConnectorStream, ConnectorError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        return self.http.poll_ready(cx).map_err(|err| err.into());
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let is_https = uri.scheme_str() == Some("https");
+        let host = uri.host().unwrap_or("").trim_matches(|c| c == '[' || c == ']').to_string();
+        let port = uri.port_u16().unwrap_or(if is_https { 443 } else { 80 });
+        let server_name = self.sni.clone().unwrap_or_else(|| host.clone());
+        let tls = self.tls.clone();
+
+        let proxy = self.proxy.clone().filter(|proxy| !proxy.bypass(&host));
+        let forwarding = proxy.as_ref().map(|proxy| proxy.forwards(&uri)).unwrap_or(false);
+        let connecting = match &proxy {
+            Some(proxy) => self.http.call(proxy.uri()),
+            None => self.http.call(uri),
+        };
+        // the proxy resolves the target itself, so hosts overrides are applied before tunneling
+        let target = match self.resolver.host_override(&host) {
+            Some(ip) => ip.to_string(),
+            None => host,
+        };
+
+        return Box::pin(async move {
+            let started_at = Instant::now();
+            let mut stream = connecting.await?;
+            if let Some(proxy) = proxy.filter(|_| !forwarding) {
+                stream = proxy.tunnel(stream, &target, port).await?;
+            }
+            let connect_time = started_at.elapsed();
+
+            let (stream, tls_time) = if is_https {
+                let stream = tls.connect(&server_name, stream).await?;
+                (MaybeTlsStream::Tls(stream), Some(started_at.elapsed() - connect_time))
+            } else {
+                (MaybeTlsStream::Plain(stream), None)
+            };
+            return Ok(ConnectorStream {
+                stream,
+                info: ConnectionInfo::new(connect_time, tls_time),
+                forwarding,
+            });
+        });
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+use crate::config::{Branch, Condition};
+use crate::runner::TaskResult;
+
+// what control flow tasks can look at, kept for the whole life of a user
+pub struct FlowState {
+    variables: HashMap<String, String>,
+    last_status: Option<u16>,
+    last_failed: bool,
+    once_done: HashSet<String>,
+}
+
+impl FlowState {
+    pub fn new() -> FlowState {
+        return FlowState {
+            variables: HashMap::new(),
+            last_status: None,
+            last_failed: false,
+            once_done: HashSet::new(),
+        };
+    }
+
+    pub fn record(&mut self, result: &TaskResult) {
+        self.last_status = result.status;
+        self.last_failed = !result.success;
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    // true the first time it is called for the given name
+    pub fn mark_once(&mut self, name: &str) -> bool {
+        return self.once_done.insert(name.to_string());
+    }
+
+    pub fn evaluate(&self, condition: &Condition) -> bool {
+        return match condition {
+            Condition::LastStatus(status) => self.last_status == Some(*status),
+            Condition::LastRequestFailed => self.last_failed,
+            Condition::Variable { name, value } => self.variables.get(name) == Some(value),
+            Condition::Not(condition) => !self.evaluate(condition),
+            Condition::All(conditions) => conditions.iter().all(|condition| self.evaluate(condition)),
+            Condition::Any(conditions) => conditions.iter().any(|condition| self.evaluate(condition)),
+        };
+    }
+}
+
+pub fn pick_branch(branches: &[Branch]) -> Option<&Branch> {
+    let total: usize = branches.iter().map(|branch| branch.weight).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = rand::thread_rng().gen_range(0..total);
+    for branch in branches {
+        if point < branch.weight {
+            return Some(branch);
+        }
+        point -= branch.weight;
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ErrorType;
+
+    fn variable(name: &str, value: &str) -> Condition {
+        return Condition::Variable { name: name.to_string(), value: value.to_string() };
+    }
+
+    fn branches(weights: &[usize]) -> Vec<Branch> {
+        return weights.iter().map(|weight| Branch { weight: *weight, tasks: vec![] }).collect();
+    }
+
+    #[test]
+    fn evaluates_the_last_response() {
+        let mut flow = FlowState::new();
+        assert!(!flow.evaluate(&Condition::LastStatus(200)));
+        assert!(!flow.evaluate(&Condition::LastRequestFailed));
+
+        flow.record(&TaskResult::request("default", "GET /", 10).failed(Some(503), ErrorType::Request5xx));
+        assert!(flow.evaluate(&Condition::LastStatus(503)));
+        assert!(flow.evaluate(&Condition::LastRequestFailed));
+
+        flow.record(&TaskResult::request("default", "GET /", 10));
+        assert!(flow.evaluate(&Condition::LastStatus(200)));
+        assert!(!flow.evaluate(&Condition::LastRequestFailed));
+    }
+
+    #[test]
+    fn evaluates_variables_and_combinations() {
+        let mut flow = FlowState::new();
+        assert!(!flow.evaluate(&variable("logged_in", "yes")));
+        flow.set("logged_in", "yes");
+        assert!(flow.evaluate(&variable("logged_in", "yes")));
+        assert!(!flow.evaluate(&variable("logged_in", "no")));
+
+        let not = Condition::Not(Box::new(variable("logged_in", "no")));
+        assert!(flow.evaluate(&not));
+        assert!(flow.evaluate(&Condition::All(vec![variable("logged_in", "yes"), not.clone()])));
+        assert!(!flow.evaluate(&Condition::All(vec![variable("logged_in", "yes"), variable("cart", "full")])));
+        assert!(flow.evaluate(&Condition::Any(vec![variable("cart", "full"), variable("logged_in", "yes")])));
+        assert!(flow.evaluate(&Condition::All(vec![])));
+        assert!(!flow.evaluate(&Condition::Any(vec![])));
+    }
+
+    #[test]
+    fn once_is_marked_a_single_time() {
+        let mut flow = FlowState::new();
+        assert!(flow.mark_once("login"));
+        assert!(!flow.mark_once("login"));
+        assert!(flow.mark_once("logout"));
+    }
+
+    #[test]
+    fn picks_only_weighted_branches() {
+        let branches = branches(&[1, 0, 3]);
+        let mut picked = [0; 3];
+        for _ in 0..1000 {
+            let branch = pick_branch(&branches).unwrap();
+            let index = branches.iter().position(|candidate| std::ptr::eq(candidate, branch)).unwrap();
+            picked[index] += 1;
+        }
+        assert_eq!(picked[1], 0);
+        assert!(picked[0] > 0 && picked[2] > picked[0]);
+    }
+
+    #[test]
+    fn picks_nothing_without_weights() {
+        assert!(pick_branch(&branches(&[0, 0])).is_none());
+        assert!(pick_branch(&branches(&[])).is_none());
+    }
+}
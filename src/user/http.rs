@@ -1,15 +1,23 @@
 use std::collections::HashMap;
+use std::time::SystemTime;
 
-use hyper::client::HttpConnector;
-use hyper_tls::HttpsConnector;
+use hyper::client::connect::HttpInfo;
 use hyper::http::{Error};
 use hyper::header::{GetAll, HeaderValue};
-use hyper::{Client, Request, Method, Uri, Body};
+use hyper::{Request, Method, Body};
+use hyper::body::Bytes;
+use hyper::http::response::Parts;
 use url::Url;
 use tokio::time::{Duration, Instant, sleep, timeout};
-use tokio::sync::mpsc::{Sender};
-use crate::config::{Schedule, Task, HttpMethod, RequestDetails, RequestData, Body as BodyType, Url as TaskUrl};
-use crate::runner::{TaskResult, ErrorType, UserResult};
+use futures::future::BoxFuture;
+use crate::config::{Scenario, Schedule, Task, LoopCondition, ThinkTime, HttpMethod, RequestDetails, RequestData, CompressionConfig, Body as BodyType, Url as TaskUrl};
+use crate::runner::{TaskResult, TaskKind, ErrorType, UserChannels, UserStatus};
+use super::{HttpClient, UserClient, UserLimiters};
+use super::connector::ConnectionInfo;
+use super::compression;
+use super::retry::{should_retry, backoff_delay};
+use super::think::think_duration;
+use super::flow::{FlowState, pick_branch};
 
 type CookiesStore = HashMap<String, String>;
 
@@ -35,12 +43,12 @@ fn to_hyper_method(method: &HttpMethod) -> Method {
     }
 }
 
-fn build_request(url_details: &TaskUrl, method: &HttpMethod, data: &RequestData, cookies_store: &CookiesStore) -> Result<Request<Body>, Error> {
+fn build_request(url_details: &TaskUrl, method: &HttpMethod, data: &RequestData, cookies_store: &CookiesStore, compression: &CompressionConfig) -> Result<Request<Body>, Error> {
     let mut builder = Request::builder()
         .method(to_hyper_method(method));
 
     let mut url = url_details.url.clone();
-    if url_details.args.len() > 0 {
+    if !url_details.args.is_empty() {
         if let Some(params) = &data.params {
             for arg in url_details.args.iter() {
                 let param_value = if let Some(value) = params.get(arg) {
@@ -68,21 +76,30 @@ fn build_request(url_details: &TaskUrl, method: &HttpMethod, data: &RequestData,
         }
     }
 
-    if cookies_store.len() > 0 {
+    if !cookies_store.is_empty() {
         let cookie_header = cookies_store.values()
-            .map(|cookie| cookie.clone())
+            .cloned()
             .collect::<Vec<String>>()
             .join("; ");
         
         builder = builder.header("cookie", cookie_header);
     }
 
+    if !compression.accept.is_empty() {
+        builder = builder.header("accept-encoding", compression::accept_encoding(&compression.accept));
+    }
+
     let request_body;
     if let Some(body) = &data.body {
         match &body {
             BodyType::Json(content) => {
                 builder = builder.header("content-type", "application/json");
-                request_body = Body::from(content.to_string());
+                if let Some(encoding) = &compression.request_body {
+                    builder = builder.header("content-encoding", compression::encoding_name(encoding));
+                    request_body = Body::from(compression::compress(encoding, content.as_bytes()).unwrap());
+                } else {
+                    request_body = Body::from(content.to_string());
+                }
             },
             BodyType::Text(content) => {
                 builder = builder.header("content-type", "text/plain");
@@ -100,107 +117,354 @@ fn build_request(url_details: &TaskUrl, method: &HttpMethod, data: &RequestData,
     return Ok(req);
 }
 
-async fn make_request(id: &String, client: &Client<HttpsConnector<HttpConnector>>, mut cookies_store: &mut CookiesStore, request: Request<Body>) -> TaskResult {
+// the body has to be read to the end before the connection can go back to the pool
+// also returns the time until the response headers arrived
+async fn send_request(client: &HttpClient, request: Request<Body>) -> Result<(Parts, Duration, Bytes), hyper::Error> {
     let started_at = Instant::now();
+    let response = client.request(request).await?;
+    let time_to_first_byte = started_at.elapsed();
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    return Ok((parts, time_to_first_byte, body));
+}
+
+fn millis(duration: Duration) -> usize {
+    return duration.as_millis().try_into().unwrap();
+}
+
+async fn make_request(user_id: usize, id: &str, scenario: &str, user_client: &UserClient, cookies_store: &mut CookiesStore, request: Request<Body>) -> TaskResult {
+    let started_at = Instant::now();
+    let timestamp = SystemTime::now();
     let url = request.uri().to_string();
 
     let result = timeout(
         Duration::from_secs(10),
-        client.request(request)
+        send_request(&user_client.client, request)
     ).await;
-    let elapsed: usize = started_at.elapsed().as_millis().try_into().unwrap();
-
-    let task_result: TaskResult = match result {
-        Ok(req_result) => match req_result {
-                Ok(response) => {
-                    store_cookies(&mut cookies_store, response.headers().get_all("set-cookie"));
-                    return TaskResult {
-                        id: id.clone(),
-                        url,
-                        duration: elapsed,
-                        success: response.status().is_success(),
-                        error: !response.status().is_success(),
-                        error_type: if response.status().is_client_error() {
-                            ErrorType::Request4xx
-                        } else if response.status().is_server_error() {
-                            ErrorType::Request5xx
-                        } else {
-                            ErrorType::RequestOther
-                        }
-                    }
-                },
-                Err(_) => TaskResult {
-                    id: id.clone(),
-                    url: url,
-                    duration: elapsed,
-                    success: false,
-                    error: true,
-                    error_type: ErrorType::Connection,
+    let elapsed = millis(started_at.elapsed());
+
+    let mut task_result = TaskResult {
+        kind: TaskKind::Request,
+        started_at: timestamp,
+        user_id,
+        id: id.to_string(),
+        group: None,
+        scenario: scenario.to_string(),
+        url,
+        duration: elapsed,
+        time_to_first_byte: None,
+        connect_time: None,
+        tls_time: None,
+        http_version: None,
+        reused_connection: None,
+        remote_addr: None,
+        local_addr: user_client.local_address.map(|address| address.to_string()),
+        status: None,
+        attempt: 1,
+        retried: false,
+        bytes_received: 0,
+        bytes_decoded: 0,
+        throttled: 0,
+        success: false,
+        error: true,
+        error_type: ErrorType::Timeout,
+    };
+
+    match result {
+        Ok(Ok((response, time_to_first_byte, body))) => {
+            store_cookies(cookies_store, response.headers.get_all("set-cookie"));
+            task_result.time_to_first_byte = Some(millis(time_to_first_byte));
+            if let Some(info) = response.extensions.get::<ConnectionInfo>() {
+                let reused = info.track_request();
+                task_result.reused_connection = Some(reused);
+                if !reused {
+                    task_result.connect_time = Some(millis(info.connect_time));
+                    task_result.tls_time = info.tls_time.map(millis);
                 }
+            }
+            task_result.remote_addr = response.extensions.get::<HttpInfo>()
+                .map(|info| info.remote_addr().ip().to_string());
+            task_result.http_version = Some(format!("{:?}", response.version));
+            task_result.status = Some(response.status.as_u16());
+            task_result.success = response.status.is_success();
+            task_result.error = !response.status.is_success();
+            task_result.error_type = if response.status.is_client_error() {
+                ErrorType::Request4xx
+            } else if response.status.is_server_error() {
+                ErrorType::Request5xx
+            } else {
+                ErrorType::RequestOther
+            };
+
+            task_result.bytes_received = body.len();
+            let content_encoding = response.headers.get("content-encoding")
+                .and_then(|value| value.to_str().ok());
+            match content_encoding {
+                Some(content_encoding) => match compression::decode(content_encoding, body.to_vec()) {
+                    Ok(decoded) => task_result.bytes_decoded = decoded.len(),
+                    Err(_) => {
+                        task_result.success = false;
+                        task_result.error = true;
+                        task_result.error_type = ErrorType::Decoding;
+                    },
+                },
+                None => task_result.bytes_decoded = body.len(),
+            }
         },
-        Err(_) => TaskResult {
-            id: id.clone(),
-            url: url,
-            duration: elapsed,
-            success: false,
-            error: true,
-            error_type: ErrorType::Timeout,
-        }
-    };
+        Ok(Err(_)) => {
+            task_result.error_type = ErrorType::Connection;
+        },
+        Err(_) => {},
+    }
 
     return task_result;
 }
 
-pub async fn http_user(schedule: Schedule) -> UserResult {
+struct User {
+    id: usize,
+    scenario: String,
+    client: UserClient,
+    limiters: UserLimiters,
+    cookies_store: CookiesStore,
+    think_time: Option<ThinkTime>,
+    flow: FlowState,
+    // names of the groups currently running, innermost last
+    groups: Vec<String>,
+    // error type of the first failed request of every running group
+    group_failures: Vec<Option<ErrorType>>,
+    channels: UserChannels,
+    sent_results: usize,
+}
 
-    let https = HttpsConnector::new();
-    let http_client = Client::builder().build::<_, hyper::Body>(https);
+impl User {
+    fn stopped(&self) -> bool {
+        return self.channels.stop_receiver.borrow().is_some();
+    }
 
-    let mut cookies_store: CookiesStore = HashMap::new();
+    // sleeps, waking up early when the run is stopped
+    async fn pause(&mut self, duration: Duration) {
+        if self.stopped() {
+            return;
+        }
+        tokio::select! {
+            _ = sleep(duration) => {},
+            _ = self.channels.stop_receiver.changed() => {},
+        }
+    }
 
-    let mut results = vec![];
-    for task in schedule.tasks {
-        match task {
-            Task::Request(details) => {
-                let RequestDetails {
-                    url,
-                    method,
-                    data,
-                    repeat
-                } = details;
+    async fn report(&mut self, result: TaskResult) {
+        // retried attempts don't fail the group, only the final ones
+        if result.kind == TaskKind::Request && !result.success && !result.retried {
+            for failure in self.group_failures.iter_mut().filter(|failure| failure.is_none()) {
+                *failure = Some(result.error_type.clone());
+            }
+        }
+        self.sent_results += 1;
+        self.channels.status_sender.send(UserStatus::Result(Box::new(result))).await.unwrap();
+    }
 
-                let task_id = url.url.clone(); // TODO better ID (for example include METHOD)
+    async fn request(&mut self, details: &RequestDetails) {
+        let RequestDetails {
+            name,
+            url,
+            method,
+            data,
+            repeat,
+            retry,
+        } = details;
 
-                let repeat = match repeat {
-                    Some(v) => v,
-                    None => 1,
-                };
+        // url template before substitution, so all values of the params share one id
+        let task_id = match name {
+            Some(name) => name.clone(),
+            None => format!("{} {}", to_hyper_method(method), url.url),
+        };
 
-                let request_data = match data {
-                    Some(data) => data,
-                    None => vec![
-                        RequestData {
-                            params: None,
-                            query: None,
-                            body: None,
-                            headers: None,
-                        }
-                    ],
-                };
-                
-                for _ in 0..repeat {
-                    for data_record in &request_data {
-                        let request = build_request(&url, &method, data_record, &cookies_store).unwrap();
-                        let result = make_request(&task_id, &http_client, &mut cookies_store, request).await;
-                        results.push(result);
+        let repeat = repeat.unwrap_or(1);
+
+        let default_data = vec![
+            RequestData {
+                params: None,
+                query: None,
+                body: None,
+                headers: None,
+            }
+        ];
+        let request_data = data.as_ref().unwrap_or(&default_data);
+
+        for _ in 0..repeat {
+            for data_record in request_data {
+                if let Some(think_time) = &self.think_time {
+                    if self.sent_results > 0 {
+                        self.pause(think_duration(think_time)).await;
+                    }
+                }
+                if self.stopped() {
+                    return;
+                }
+
+                let mut attempt = 1;
+                loop {
+                    let throttled = match self.limiters.acquire(&task_id).await {
+                        Some(throttled) => throttled,
+                        None => return,
+                    };
+                    let mut request = build_request(url, method, data_record, &self.cookies_store, &self.client.compression).unwrap();
+                    self.client.authorize_proxy(&mut request);
+                    let mut result = make_request(self.id, &task_id, &self.scenario, &self.client, &mut self.cookies_store, request).await;
+                    result.attempt = attempt;
+                    result.throttled = millis(throttled);
+                    result.group = self.groups.last().cloned();
+
+                    let retry_policy = retry.as_ref().filter(|policy| should_retry(policy, &result, attempt));
+                    result.retried = retry_policy.is_some();
+                    self.flow.record(&result);
+                    self.report(result).await;
+
+                    match retry_policy {
+                        Some(policy) if !self.stopped() => {
+                            self.pause(backoff_delay(policy, attempt)).await;
+                            attempt += 1;
+                        },
+                        _ => break,
                     }
                 }
-            },
-            Task::Wait(duration) => {
-                sleep(Duration::from_secs(duration.try_into().unwrap())).await;
             }
         }
     }
 
-    return Ok(results);
-}
\ No newline at end of file
+    async fn group(&mut self, name: &str, tasks: &[Task]) {
+        let started_at = Instant::now();
+        let timestamp = SystemTime::now();
+
+        self.groups.push(name.to_string());
+        self.group_failures.push(None);
+        self.run_tasks(tasks).await;
+        self.groups.pop();
+        let failure = self.group_failures.pop().flatten();
+
+        let result = TaskResult {
+            kind: TaskKind::Group,
+            started_at: timestamp,
+            user_id: self.id,
+            id: name.to_string(),
+            group: self.groups.last().cloned(),
+            scenario: self.scenario.clone(),
+            url: String::new(),
+            success: failure.is_none(),
+            error: failure.is_some(),
+            error_type: failure.unwrap_or(ErrorType::Internal),
+            duration: millis(started_at.elapsed()),
+            time_to_first_byte: None,
+            connect_time: None,
+            tls_time: None,
+            http_version: None,
+            reused_connection: None,
+            remote_addr: None,
+            local_addr: None,
+            status: None,
+            attempt: 1,
+            retried: false,
+            bytes_received: 0,
+            bytes_decoded: 0,
+            throttled: 0,
+        };
+        self.report(result).await;
+    }
+
+    // boxed, because control flow tasks run their nested tasks recursively
+    fn run_tasks<'a>(&'a mut self, tasks: &'a [Task]) -> BoxFuture<'a, ()> {
+        return Box::pin(async move {
+            for task in tasks {
+                if self.stopped() {
+                    return;
+                }
+                match task {
+                    Task::Request(details) => {
+                        self.request(details).await;
+                    },
+                    Task::Wait(duration) => {
+                        self.pause(Duration::from_secs((*duration).try_into().unwrap())).await;
+                    },
+                    Task::Think(think_time) => {
+                        self.pause(think_duration(think_time)).await;
+                    },
+                    Task::Loop { condition, tasks } => match condition {
+                        LoopCondition::Count(count) => {
+                            for _ in 0..*count {
+                                if self.stopped() {
+                                    break;
+                                }
+                                self.run_tasks(tasks).await;
+                            }
+                        },
+                        LoopCondition::While { condition, max_iterations } => {
+                            let mut iterations = 0;
+                            while !self.stopped() && self.flow.evaluate(condition) && max_iterations.is_none_or(|max| iterations < max) {
+                                self.run_tasks(tasks).await;
+                                iterations += 1;
+                                // a body of Set and If tasks never waits, so give the other users a turn
+                                tokio::task::yield_now().await;
+                            }
+                        },
+                    },
+                    Task::If { condition, then, otherwise } => {
+                        if self.flow.evaluate(condition) {
+                            self.run_tasks(then).await;
+                        } else {
+                            self.run_tasks(otherwise).await;
+                        }
+                    },
+                    Task::RandomChoice(branches) => {
+                        if let Some(branch) = pick_branch(branches) {
+                            self.run_tasks(&branch.tasks).await;
+                        }
+                    },
+                    Task::Once { name, tasks } => {
+                        if self.flow.mark_once(name) {
+                            self.run_tasks(tasks).await;
+                        }
+                    },
+                    Task::Set { name, value } => {
+                        self.flow.set(name, value);
+                    },
+                    Task::Group { name, tasks } => {
+                        self.group(name, tasks).await;
+                    },
+                }
+            }
+        });
+    }
+}
+
+// Runs the scenario once, sending every task result to the runner as soon as it finishes.
+pub async fn http_user(user_id: usize, scenario: Scenario, user_client: UserClient, limiters: UserLimiters, channels: UserChannels) -> Result<(), String> {
+    let started_at = Instant::now();
+
+    let Schedule {
+        tasks,
+        think_time,
+        pacing,
+    } = scenario.schedule;
+
+    let mut user = User {
+        id: user_id,
+        scenario: scenario.name,
+        client: user_client,
+        limiters,
+        cookies_store: HashMap::new(),
+        think_time,
+        flow: FlowState::new(),
+        groups: vec![],
+        group_failures: vec![],
+        channels,
+        sent_results: 0,
+    };
+    user.run_tasks(&tasks).await;
+
+    if let Some(pacing) = pacing {
+        let next_start = started_at + Duration::from_secs(pacing.try_into().unwrap());
+        user.pause(next_start.saturating_duration_since(Instant::now())).await;
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant, sleep};
+use crate::config::{RateLimit, RateLimitConfig};
+
+struct BucketState {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// Token bucket that hands out reservations, tokens can go negative and the caller waits
+// until its token is refilled, so concurrent users are served in order of arrival.
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    pub fn new(limit: &RateLimit) -> TokenBucket {
+        let burst = limit.burst.max(1) as f64;
+        return TokenBucket {
+            rate: limit.requests_per_second.max(1) as f64,
+            burst,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                updated_at: Instant::now(),
+            }),
+        };
+    }
+
+    // how long the caller has to wait before sending
+    fn reserve(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refilled = now.duration_since(state.updated_at).as_secs_f64() * self.rate;
+        state.tokens = (state.tokens + refilled).min(self.burst) - 1.0;
+        state.updated_at = now;
+
+        if state.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        return Duration::from_secs_f64(-state.tokens / self.rate);
+    }
+}
+
+pub struct RateLimiters {
+    global: Option<Arc<TokenBucket>>,
+    per_user: Option<RateLimit>,
+    per_request: Arc<HashMap<String, TokenBucket>>,
+    stop: watch::Receiver<Option<String>>,
+}
+
+impl RateLimiters {
+    pub fn new(config: &RateLimitConfig, stop: watch::Receiver<Option<String>>) -> RateLimiters {
+        return RateLimiters {
+            global: config.global.as_ref().map(|limit| Arc::new(TokenBucket::new(limit))),
+            per_user: config.per_user.clone(),
+            per_request: Arc::new(config.per_request.iter()
+                .map(|(name, limit)| (name.clone(), TokenBucket::new(limit)))
+                .collect()),
+            stop,
+        };
+    }
+
+    pub fn for_user(&self) -> UserLimiters {
+        return UserLimiters {
+            global: self.global.clone(),
+            user: self.per_user.as_ref().map(TokenBucket::new),
+            per_request: self.per_request.clone(),
+            stop: self.stop.clone(),
+        };
+    }
+}
+
+pub struct UserLimiters {
+    global: Option<Arc<TokenBucket>>,
+    user: Option<TokenBucket>,
+    per_request: Arc<HashMap<String, TokenBucket>>,
+    stop: watch::Receiver<Option<String>>,
+}
+
+impl UserLimiters {
+    // waits until every limiter that applies lets the request through, returns the time spent waiting,
+    // None when the run was stopped in the meantime and the request shouldn't be sent
+    pub async fn acquire(&self, name: &str) -> Option<Duration> {
+        let started_at = Instant::now();
+        let mut stop = self.stop.clone();
+        let buckets = [self.user.as_ref(), self.per_request.get(name), self.global.as_deref()];
+        for bucket in buckets.into_iter().flatten() {
+            if stop.borrow().is_some() {
+                return None;
+            }
+            let delay = bucket.reserve();
+            if !delay.is_zero() {
+                tokio::select! {
+                    _ = sleep(delay) => {},
+                    _ = stop.changed() => return None,
+                }
+            }
+        }
+        return Some(started_at.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(requests_per_second: usize, burst: usize) -> TokenBucket {
+        return TokenBucket::new(&RateLimit { requests_per_second, burst });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_is_served_without_waiting() {
+        let bucket = bucket(2, 3);
+        for _ in 0..3 {
+            assert_eq!(bucket.reserve(), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reservations_go_negative_and_queue_up() {
+        let bucket = bucket(2, 1);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(), Duration::from_millis(1000));
+        assert_eq!(bucket.reserve(), Duration::from_millis(1500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_pays_back_reservations_first() {
+        let bucket = bucket(1, 1);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::from_secs(1));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        // the refilled token went to the previous reservation
+        assert_eq!(bucket.reserve(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_is_capped_at_burst() {
+        let bucket = bucket(10, 2);
+        bucket.reserve();
+        bucket.reserve();
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::from_millis(100));
+    }
+
+    fn limiters(requests_per_second: usize) -> (RateLimiters, watch::Sender<Option<String>>) {
+        let (stop_sender, stop_receiver) = watch::channel(None);
+        let config = RateLimitConfig {
+            global: None,
+            per_user: Some(RateLimit { requests_per_second, burst: 1 }),
+            per_request: HashMap::new(),
+        };
+        return (RateLimiters::new(&config, stop_receiver), stop_sender);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_the_token() {
+        let (limiters, _stop) = limiters(1);
+        let user = limiters.for_user();
+        assert_eq!(user.acquire("GET /").await, Some(Duration::ZERO));
+        assert_eq!(user.acquire("GET /").await, Some(Duration::from_secs(1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_ends_the_wait_for_a_token() {
+        let (limiters, stop) = limiters(1);
+        let user = limiters.for_user();
+        assert!(user.acquire("GET /").await.is_some());
+
+        let started_at = Instant::now();
+        let waiting = tokio::spawn(async move { user.acquire("GET /").await });
+        tokio::time::advance(Duration::from_millis(100)).await;
+        stop.send(Some("aborted".to_string())).unwrap();
+        assert_eq!(waiting.await.unwrap(), None);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+}
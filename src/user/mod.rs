@@ -1,3 +1,14 @@
+mod client;
+mod compression;
+mod connector;
+mod flow;
 mod http;
+mod limiter;
+mod proxy;
+mod resolver;
+mod retry;
+mod think;
 
-pub use http::*;
\ No newline at end of file
+pub use client::*;
+pub use http::*;
+pub use limiter::*;
\ No newline at end of file
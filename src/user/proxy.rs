@@ -0,0 +1,165 @@
+use hyper::Uri;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use crate::config::{ProxyConfig, ProxyKind};
+use super::connector::ConnectorError;
+
+const MAX_CONNECT_RESPONSE_SIZE: usize = 8192;
+
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    config: ProxyConfig,
+    uri: Uri,
+}
+
+impl Proxy {
+    pub fn new(config: &ProxyConfig) -> Result<Proxy, String> {
+        let uri = format!("http://{}", config.address).parse()
+            .map_err(|err| format!("Invalid proxy address {}: {}", config.address, err))?;
+
+        return Ok(Proxy {
+            config: config.clone(),
+            uri,
+        });
+    }
+
+    pub fn uri(&self) -> Uri {
+        return self.uri.clone();
+    }
+
+    // hosts as in the uri, ip v6 addresses without brackets
+    pub fn bypass(&self, host: &str) -> bool {
+        return self.config.no_proxy.iter().any(|pattern| {
+            if pattern == "*" {
+                return true;
+            }
+            if let Some(domain) = pattern.strip_prefix('.') {
+                return host == domain || host.ends_with(pattern.as_str());
+            }
+            return host == pattern;
+        });
+    }
+
+    // plain http requests go to an http proxy in absolute-form, only https is tunneled with CONNECT,
+    // which forward proxies often allow to port 443 only
+    pub fn forwards(&self, uri: &Uri) -> bool {
+        let host = uri.host().unwrap_or("").trim_matches(|c| c == '[' || c == ']');
+        return matches!(self.config.kind, ProxyKind::Http) && uri.scheme_str() != Some("https") && !self.bypass(host);
+    }
+
+    // value of the Proxy-Authorization header
+    pub fn authorization(&self) -> Option<String> {
+        return self.config.auth.as_ref()
+            .map(|auth| format!("Basic {}", base64::encode(format!("{}:{}", auth.username, auth.password))));
+    }
+
+    // turns a connection to the proxy into a connection to host:port
+    pub async fn tunnel(&self, stream: TcpStream, host: &str, port: u16) -> Result<TcpStream, ConnectorError> {
+        return match self.config.kind {
+            ProxyKind::Http => self.http_connect(stream, host, port).await,
+            ProxyKind::Socks5 => self.socks5_connect(stream, host, port).await,
+        };
+    }
+
+    async fn http_connect(&self, mut stream: TcpStream, host: &str, port: u16) -> Result<TcpStream, ConnectorError> {
+        let authority = if host.contains(':') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        };
+
+        let mut request = format!("CONNECT {a} HTTP/1.1\r\nHost: {a}\r\n", a = authority);
+        if let Some(authorization) = self.authorization() {
+            request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // the target doesn't send anything before we do, so the proxy response is all there is to read
+        let mut response = vec![];
+        let mut buffer = [0; 1024];
+        while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 {
+                return Err("Proxy closed the connection during CONNECT".into());
+            }
+            response.extend_from_slice(&buffer[..read]);
+            if response.len() > MAX_CONNECT_RESPONSE_SIZE {
+                return Err("Proxy CONNECT response is too large".into());
+            }
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or("");
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(format!("Proxy refused CONNECT to {}: {}", authority, status_line).into());
+        }
+
+        return Ok(stream);
+    }
+
+    async fn socks5_connect(&self, stream: TcpStream, host: &str, port: u16) -> Result<TcpStream, ConnectorError> {
+        let stream = match &self.config.auth {
+            Some(auth) => Socks5Stream::connect_with_password_and_socket(stream, (host, port), &auth.username, &auth.password).await?,
+            None => Socks5Stream::connect_with_socket(stream, (host, port)).await?,
+        };
+        return Ok(stream.into_inner());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyAuth;
+
+    fn proxy(kind: ProxyKind, no_proxy: &[&str]) -> Proxy {
+        return Proxy::new(&ProxyConfig {
+            kind,
+            address: "127.0.0.1:3128".to_string(),
+            auth: None,
+            no_proxy: no_proxy.iter().map(|pattern| pattern.to_string()).collect(),
+        }).unwrap();
+    }
+
+    #[test]
+    fn bypass_matches_exact_hosts() {
+        let proxy = proxy(ProxyKind::Http, &["localhost", "10.0.0.1"]);
+        assert!(proxy.bypass("localhost"));
+        assert!(proxy.bypass("10.0.0.1"));
+        assert!(!proxy.bypass("api.localhost"));
+        assert!(!proxy.bypass("example.com"));
+    }
+
+    #[test]
+    fn bypass_matches_domains_and_subdomains() {
+        let proxy = proxy(ProxyKind::Http, &[".example.com"]);
+        assert!(proxy.bypass("example.com"));
+        assert!(proxy.bypass("api.example.com"));
+        assert!(!proxy.bypass("badexample.com"));
+        assert!(!proxy.bypass("example.org"));
+    }
+
+    #[test]
+    fn bypass_wildcard_matches_everything() {
+        assert!(proxy(ProxyKind::Http, &["*"]).bypass("example.com"));
+        assert!(!proxy(ProxyKind::Http, &[]).bypass("example.com"));
+    }
+
+    #[test]
+    fn forwards_only_plain_http_through_http_proxies() {
+        let http = proxy(ProxyKind::Http, &["localhost"]);
+        assert!(http.forwards(&"http://example.com/users".parse().unwrap()));
+        assert!(!http.forwards(&"https://example.com/users".parse().unwrap()));
+        assert!(!http.forwards(&"http://localhost:8080/".parse().unwrap()));
+        assert!(!proxy(ProxyKind::Socks5, &[]).forwards(&"http://example.com/".parse().unwrap()));
+    }
+
+    #[test]
+    fn authorization_uses_basic_credentials() {
+        let mut config = proxy(ProxyKind::Http, &[]).config;
+        assert_eq!(Proxy::new(&config).unwrap().authorization(), None);
+        config.auth = Some(ProxyAuth { username: "user".to_string(), password: "secret".to_string() });
+        assert_eq!(Proxy::new(&config).unwrap().authorization(), Some("Basic dXNlcjpzZWNyZXQ=".to_string()));
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use hyper::client::connect::dns::Name;
+use hyper::service::Service;
+use tokio::net::lookup_host;
+use crate::config::DnsConfig;
+
+#[derive(Clone)]
+pub struct Resolver {
+    hosts: Arc<HashMap<String, Vec<IpAddr>>>,
+    round_robin: bool,
+    // shared by all clones, so the rotation is spread across users
+    counter: Arc<AtomicUsize>,
+}
+
+impl Resolver {
+    pub fn new(config: &DnsConfig) -> Resolver {
+        return Resolver {
+            hosts: Arc::new(config.hosts.clone()),
+            round_robin: config.round_robin,
+            counter: Arc::new(AtomicUsize::new(0)),
+        };
+    }
+
+    // first overridden address of the host, used when the proxy connects on our behalf
+    pub fn host_override(&self, host: &str) -> Option<IpAddr> {
+        return self.hosts.get(host).and_then(|addresses| addresses.first().copied());
+    }
+
+    async fn resolve(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+        let mut addresses: Vec<SocketAddr> = match self.hosts.get(host) {
+            Some(addresses) => addresses.iter().map(|ip| SocketAddr::new(*ip, 0)).collect(),
+            None => lookup_host((host, 0)).await?.collect(),
+        };
+
+        if self.round_robin && !addresses.is_empty() {
+            let shift = self.counter.fetch_add(1, Ordering::Relaxed) % addresses.len();
+            addresses.rotate_left(shift);
+        }
+
+        return Ok(addresses);
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        return Poll::Ready(Ok(()));
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolver = self.clone();
+        return Box::pin(async move {
+            let addresses = resolver.resolve(name.as_str()).await?;
+            return Ok(addresses.into_iter());
+        });
+    }
+}
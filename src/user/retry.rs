@@ -0,0 +1,103 @@
+use rand::Rng;
+use tokio::time::Duration;
+use crate::config::RetryPolicy;
+use crate::runner::TaskResult;
+
+pub fn should_retry(policy: &RetryPolicy, result: &TaskResult, attempt: usize) -> bool {
+    if attempt >= policy.max_attempts || result.success {
+        return false;
+    }
+
+    let status_matches = match result.status {
+        Some(status) => policy.on_status.contains(&status),
+        None => false,
+    };
+
+    return status_matches || (result.error && policy.on_errors.contains(&result.error_type));
+}
+
+// exponential backoff, attempt is the number of the attempt that just failed (starting at 1)
+pub fn backoff_delay(policy: &RetryPolicy, attempt: usize) -> Duration {
+    let exponent: u32 = (attempt - 1).min(31).try_into().unwrap();
+    let mut delay = policy.backoff.saturating_mul(2usize.saturating_pow(exponent));
+    delay = delay.min(policy.max_backoff);
+
+    if policy.jitter && delay > 0 {
+        delay = rand::thread_rng().gen_range(0..=delay);
+    }
+
+    return Duration::from_millis(delay.try_into().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ErrorType;
+
+    fn policy(backoff: usize, max_backoff: usize, jitter: bool) -> RetryPolicy {
+        return RetryPolicy {
+            max_attempts: 3,
+            backoff,
+            max_backoff,
+            jitter,
+            on_errors: vec![ErrorType::Timeout, ErrorType::Connection],
+            on_status: vec![429, 503],
+        };
+    }
+
+    fn result() -> TaskResult {
+        return TaskResult::request("default", "GET /", 10);
+    }
+
+    #[test]
+    fn retries_matching_statuses_and_error_types() {
+        let policy = policy(100, 1000, false);
+        assert!(should_retry(&policy, &result().failed(Some(503), ErrorType::Request5xx), 1));
+        assert!(should_retry(&policy, &result().failed(Some(429), ErrorType::Request4xx), 1));
+        assert!(should_retry(&policy, &result().failed(None, ErrorType::Timeout), 1));
+        assert!(should_retry(&policy, &result().failed(None, ErrorType::Connection), 2));
+    }
+
+    #[test]
+    fn does_not_retry_other_failures_or_successes() {
+        let policy = policy(100, 1000, false);
+        assert!(!should_retry(&policy, &result(), 1));
+        assert!(!should_retry(&policy, &result().failed(Some(500), ErrorType::Request5xx), 1));
+        assert!(!should_retry(&policy, &result().failed(Some(404), ErrorType::Request4xx), 1));
+        assert!(!should_retry(&policy, &result().failed(Some(200), ErrorType::Decoding), 1));
+    }
+
+    #[test]
+    fn stops_after_max_attempts() {
+        let policy = policy(100, 1000, false);
+        let failed = result().failed(Some(503), ErrorType::Request5xx);
+        assert!(should_retry(&policy, &failed, 2));
+        assert!(!should_retry(&policy, &failed, 3));
+        assert!(!should_retry(&policy, &failed, 4));
+    }
+
+    #[test]
+    fn backoff_doubles_after_every_attempt() {
+        let policy = policy(100, 10_000, false);
+        let delays: Vec<u128> = (1..=5).map(|attempt| backoff_delay(&policy, attempt).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1600]);
+    }
+
+    #[test]
+    fn backoff_is_capped_by_max_backoff() {
+        let policy = policy(100, 500, false);
+        assert_eq!(backoff_delay(&policy, 3), Duration::from_millis(400));
+        assert_eq!(backoff_delay(&policy, 4), Duration::from_millis(500));
+        assert_eq!(backoff_delay(&policy, 100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn jitter_stays_within_the_backoff() {
+        let policy = policy(100, 500, true);
+        for attempt in 1..=10 {
+            let limit = Duration::from_millis((100 << (attempt - 1).min(3)).min(500));
+            assert!(backoff_delay(&policy, attempt) <= limit);
+        }
+        assert_eq!(backoff_delay(&RetryPolicy { backoff: 0, ..policy }, 1), Duration::ZERO);
+    }
+}
@@ -0,0 +1,29 @@
+use rand::Rng;
+use rand_distr::{Distribution, Exp, Normal};
+use tokio::time::Duration;
+use crate::config::ThinkTime;
+
+pub fn think_duration(think_time: &ThinkTime) -> Duration {
+    let mut rng = rand::thread_rng();
+
+    let millis: f64 = match think_time {
+        ThinkTime::Constant(value) => *value as f64,
+        ThinkTime::Uniform { min, max } => {
+            if max <= min {
+                *min as f64
+            } else {
+                rng.gen_range(*min..=*max) as f64
+            }
+        },
+        ThinkTime::Normal { mean, std_dev } => match Normal::new(*mean as f64, *std_dev as f64) {
+            Ok(normal) => normal.sample(&mut rng),
+            Err(_) => *mean as f64,
+        },
+        ThinkTime::Exponential { mean } => match Exp::new(1.0 / *mean as f64) {
+            Ok(exp) => exp.sample(&mut rng),
+            Err(_) => *mean as f64,
+        },
+    };
+
+    return Duration::from_millis(millis.max(0.0).round() as u64);
+}
@@ -0,0 +1,40 @@
+// Stand-in metrics backends for the push reporters, prints everything it receives.
+// InfluxDB: HTTP on 8086 (/write, /api/v2/write) and UDP on 8089
+// StatsD / DogStatsD: UDP on 8125
+// OTLP: HTTP on 4318 (/v1/metrics, json encoding)
+const http = require('http');
+const dgram = require('dgram');
+
+function udpListener(name, port) {
+  const socket = dgram.createSocket('udp4');
+  socket.on('message', (msg, rinfo) => {
+    console.log(`==================== ${name} UDP ${rinfo.address}:${rinfo.port} (${msg.length} bytes) ==================`);
+    console.log(msg.toString());
+  });
+  socket.bind(port);
+}
+
+function httpListener(name, port, paths, status) {
+  http.createServer((req, res) => {
+    let body = '';
+    req.on('data', chunk => body += chunk);
+    req.on('end', () => {
+      const path = req.url.split('?')[0];
+      if (req.method !== 'POST' || !paths.includes(path)) {
+        res.writeHead(404);
+        res.end();
+        return;
+      }
+      console.log(`==================== ${name} ${req.method} ${req.url} ==================`);
+      console.log('Headers: ', req.headers);
+      console.log(body);
+      res.writeHead(status, { 'content-type': 'application/json' });
+      res.end(status === 204 ? undefined : '{}');
+    });
+  }).listen(port);
+}
+
+httpListener('InfluxDB', 8086, ['/write', '/api/v2/write'], 204);
+udpListener('InfluxDB', 8089);
+udpListener('StatsD', 8125);
+httpListener('OTLP', 4318, ['/v1/metrics'], 200);
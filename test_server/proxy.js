@@ -0,0 +1,124 @@
+// Stand-in egress proxy: HTTP forwarding and CONNECT on 3128, SOCKS5 on 1080.
+// Set PROXY_AUTH=user:password to require credentials on both.
+const http = require('http');
+const net = require('net');
+
+const auth = process.env.PROXY_AUTH;
+
+function pipe(client, host, port, onConnected) {
+  const upstream = net.connect(port, host, () => {
+    onConnected();
+    upstream.pipe(client);
+    client.pipe(upstream);
+  });
+  upstream.on('error', err => {
+    console.log('Upstream error', host, port, err.message);
+    client.destroy();
+  });
+  client.on('error', () => upstream.destroy());
+}
+
+function authorized(req) {
+  return !auth || req.headers['proxy-authorization'] === 'Basic ' + Buffer.from(auth).toString('base64');
+}
+
+// plain http requests arrive in absolute-form and are forwarded as they are
+const httpProxy = http.createServer((req, res) => {
+  console.log('==================== FORWARD ==================', req.method, req.url);
+  if (!authorized(req)) {
+    res.writeHead(407);
+    res.end();
+    return;
+  }
+  const headers = { ...req.headers };
+  delete headers['proxy-authorization'];
+  const upstream = http.request(req.url, { method: req.method, headers }, response => {
+    res.writeHead(response.statusCode, response.headers);
+    response.pipe(res);
+  });
+  upstream.on('error', err => {
+    console.log('Upstream error', req.url, err.message);
+    res.writeHead(502);
+    res.end();
+  });
+  req.pipe(upstream);
+});
+
+httpProxy.on('connect', (req, client) => {
+  console.log('==================== CONNECT ==================', req.url);
+  if (!authorized(req)) {
+    client.end('HTTP/1.1 407 Proxy Authentication Required\r\n\r\n');
+    return;
+  }
+  const url = new URL(`http://${req.url}`);
+  pipe(client, url.hostname.replace(/^\[|\]$/g, ''), url.port, () => {
+    client.write('HTTP/1.1 200 Connection Established\r\n\r\n');
+  });
+});
+
+httpProxy.listen(3128);
+
+function readBytes(socket, count) {
+  return new Promise((resolve, reject) => {
+    const read = () => {
+      const chunk = socket.read(count);
+      if (chunk !== null) {
+        socket.removeListener('readable', read);
+        socket.removeListener('end', reject);
+        resolve(chunk);
+      }
+    };
+    socket.on('readable', read);
+    socket.on('end', reject);
+    read();
+  });
+}
+
+async function socks5(client) {
+  const [version, numMethods] = await readBytes(client, 2);
+  const methods = await readBytes(client, numMethods);
+  const method = auth ? 0x02 : 0x00;
+  if (version !== 0x05 || !methods.includes(method)) {
+    client.end(Buffer.from([0x05, 0xff]));
+    return;
+  }
+  client.write(Buffer.from([0x05, method]));
+
+  if (auth) {
+    const [, userLength] = await readBytes(client, 2);
+    const user = (await readBytes(client, userLength)).toString();
+    const [passwordLength] = await readBytes(client, 1);
+    const password = (await readBytes(client, passwordLength)).toString();
+    if (`${user}:${password}` !== auth) {
+      client.end(Buffer.from([0x01, 0x01]));
+      return;
+    }
+    client.write(Buffer.from([0x01, 0x00]));
+  }
+
+  const [, command, , addressType] = await readBytes(client, 4);
+  let host;
+  if (addressType === 0x01) {
+    host = Array.from(await readBytes(client, 4)).join('.');
+  } else if (addressType === 0x03) {
+    const [length] = await readBytes(client, 1);
+    host = (await readBytes(client, length)).toString();
+  } else {
+    const address = await readBytes(client, 16);
+    host = address.toString('hex').match(/.{4}/g).join(':');
+  }
+  const port = (await readBytes(client, 2)).readUInt16BE(0);
+
+  console.log('==================== SOCKS5 ==================', host, port);
+  if (command !== 0x01) {
+    client.end(Buffer.from([0x05, 0x07, 0x00, 0x01, 0, 0, 0, 0, 0, 0]));
+    return;
+  }
+  pipe(client, host, port, () => {
+    client.write(Buffer.from([0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]));
+  });
+}
+
+net.createServer(client => {
+  socks5(client).catch(() => client.destroy());
+}).listen(1080);